@@ -1,15 +1,185 @@
+use balloonship_worldgen::change::{ChangeLog, LayerResources};
+use balloonship_worldgen::dungeon::{DungeonTile, Dungeons};
+use balloonship_worldgen::edit::{Brush, BrushShape, Edit};
+use balloonship_worldgen::history::History;
+use balloonship_worldgen::layer::glyph::{GlyphAtlas, GlyphManifest, GlyphValue};
+use balloonship_worldgen::layer::layers::biome::Biome;
+use balloonship_worldgen::layer::layers::detail::TerrainDetail;
+use balloonship_worldgen::layer::layers::flora::Flora;
+use balloonship_worldgen::layer::layers::mineral::Mineral;
+use balloonship_worldgen::layer::layers::special::Special;
+use balloonship_worldgen::layer::layers::terrain::TerrainFeature;
+use balloonship_worldgen::layer::layers::territory::TerritoryId;
+use balloonship_worldgen::layer::layers::urban::Urban;
+use balloonship_worldgen::layer::layers::water::WaterType;
+use balloonship_worldgen::layer::render::{CellSprites, LayerRender};
+use balloonship_worldgen::layer::{GridPosition, Layer, LayerValue, WorldCell, WorldPosition};
+use balloonship_worldgen::names::{PlaceKind, WorldNames};
+use balloonship_worldgen::plugin::{LayerChanged, WorldGenPlugin, WorldGenerated};
+use balloonship_worldgen::portal::PortalNetwork;
+use balloonship_worldgen::season::{Season, SeasonalLayers};
+use balloonship_worldgen::simulation::SimulationClock;
+use balloonship_worldgen::stamp::Stamp;
+use balloonship_worldgen::stats::WorldStats;
+use balloonship_worldgen::world::{LayerKind, WorldGenConfig};
+use bevy::ecs::system::SystemParam;
+use bevy::input::mouse::MouseWheel;
 use bevy::prelude::*;
-pub mod layer;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+/// The number of grid lines drawn along each axis.
+const GRID_SIZE: u32 = 16;
+
+/// The size of a grid line cell in pixels.
+const CELL_SIZE: f32 = 64.0;
+
+/// The glyph manifest describing the texture atlas used for point-like layers.
+const GLYPH_MANIFEST: &str = "glyphs.json";
+
+/// The directory of the stamps placed on every world, relative to the assets directory.
+const STAMPS: &str = "stamps";
 
 #[derive(Component)]
 struct GridLine;
 
+/// Marks sprites drawn over whole layers, like borders, so they can be cleared on regeneration.
+#[derive(Component)]
+struct LayerSprite;
+
+/// The sprites drawn for the cells of each layer on the map, so that changed cells can be
+/// redrawn alone.
+#[derive(Resource, Default)]
+struct MapSprites(HashMap<LayerKind, CellSprites>);
+
+/// Marks the interior of a dungeon opened by clicking its cell, drawn over the map.
+#[derive(Component)]
+struct DungeonView;
+
+/// Which map the playground draws.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq)]
+enum MapView {
+	/// The physical world: water, terrain, biomes and everything on them.
+	#[default]
+	Physical,
+	/// Kingdoms and their borders over the water, with settlements.
+	Political,
+	/// The terrain with special sites and the links between portals.
+	Debug,
+}
+
+impl MapView {
+	/// The layers drawn in the view, from bottom to top.
+	fn layers(&self) -> &'static [LayerKind] {
+		match self {
+			MapView::Physical => &[
+				LayerKind::Water,
+				LayerKind::Terrain,
+				LayerKind::Biome,
+				LayerKind::Detail,
+				LayerKind::Mineral,
+				LayerKind::Flora,
+				LayerKind::Urban,
+				LayerKind::Special,
+			],
+			MapView::Political => &[LayerKind::Water, LayerKind::Territory, LayerKind::Urban],
+			MapView::Debug => &[LayerKind::Water, LayerKind::Terrain, LayerKind::Special],
+		}
+	}
+}
+
+/// The tool the [Editor] paints with.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Tool {
+	/// Paints under the cursor while the mouse button is held.
+	#[default]
+	Brush,
+	/// Fills the area of equal cells clicked.
+	Fill,
+	/// Fills the rectangle dragged out.
+	Rectangle,
+}
+
+/// The map editor, painting a value picked from the palette of a layer onto the map.
+#[derive(Resource, Debug)]
+struct Editor {
+	enabled: bool,
+	/// The layer being painted.
+	kind: LayerKind,
+	/// The index of the value being painted in the palette of the layer.
+	variant: usize,
+	tool: Tool,
+	brush: Brush,
+	/// Where the rectangle being dragged out started.
+	anchor: Option<WorldPosition>,
+}
+
+impl Default for Editor {
+	fn default() -> Self {
+		Self {
+			enabled: false,
+			kind: LayerKind::Water,
+			variant: 0,
+			tool: Tool::default(),
+			brush: Brush { shape: BrushShape::Round, radius: 1 },
+			anchor: None,
+		}
+	}
+}
+
+/// A place name drawn over the map, shown only within a range of camera zoom levels.
+#[derive(Component)]
+struct PlaceLabel {
+	/// The camera projection scales at which the label is shown.
+	zoom: RangeInclusive<f32>,
+}
+
 fn main() {
-	App::new().add_plugins(DefaultPlugins).add_systems(Startup, setup).run();
+	App::new()
+		.add_plugins((DefaultPlugins, WorldGenPlugin))
+		.init_resource::<MapView>()
+		.init_resource::<MapSprites>()
+		.init_resource::<Editor>()
+		.add_systems(Startup, setup)
+		.add_systems(
+			Update,
+			(
+				reseed_world,
+				toggle_view,
+				cycle_season,
+				control_simulation,
+				log_stats,
+				zoom_camera,
+				render_world,
+				render_labels,
+				show_labels,
+				open_dungeon,
+				control_editor,
+				paint_map,
+				undo_changes,
+			),
+		)
+		.run();
+}
+
+/// Resolves a file in the assets directory the same way the [AssetPlugin] does.
+fn asset_path(path: &str) -> PathBuf {
+	std::env::var("BEVY_ASSET_ROOT")
+		.or_else(|_| std::env::var("CARGO_MANIFEST_DIR"))
+		.map(PathBuf::from)
+		.unwrap_or_default()
+		.join("assets")
+		.join(path)
 }
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-	// Camera
+fn setup(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	mut atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+	mut config: ResMut<WorldGenConfig>,
+) {
+	// Camera, centered on the world origin
 	commands.spawn((Camera2d, Transform::default()));
 
 	// Background
@@ -19,25 +189,18 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
 			custom_size: Some(Vec2::new(2000.0, 2000.0)),
 			..default()
 		},
-		Transform::default(),
+		Transform::from_xyz(0.0, 0.0, -1.0),
 	));
 
-	// Initialize noise generator
-	let noise_gen = NoiseGenerator::new();
-
-	// Render layers in order from bottom to top with proper scales
-	// Base layers (scale 60 = 16x16 grid)
-	render_layer_grid(generate_water_layer(&noise_gen, 60), &mut commands);
-	render_layer_grid(generate_terrain_layer(&noise_gen, 60), &mut commands);
-	render_layer_grid(generate_biome_layer(&noise_gen, 60), &mut commands);
+	// Load the glyph atlas, falling back to colored squares if the manifest is unusable
+	let manifest = GlyphManifest::load(asset_path(GLYPH_MANIFEST)).unwrap_or_else(|error| {
+		warn!("{error}, rendering glyphs as colored squares");
+		GlyphManifest::default()
+	});
+	commands.insert_resource(GlyphAtlas::new(&manifest, &asset_server, &mut atlas_layouts));
 
-	// Detail layers (scale 58 = 64x64 grid)
-	render_layer_grid(generate_detail_layer(&noise_gen, 58), &mut commands);
-	render_layer_grid(generate_flora_layer(&noise_gen, 58), &mut commands);
-	render_layer_grid(generate_urban_layer(&noise_gen, 58), &mut commands);
-
-	// Special layer (scale 56 = 256x256 grid)
-	render_layer_grid(generate_special_layer(&noise_gen, 56), &mut commands);
+	// Stamp the hand-made places onto every world
+	config.stamps = load_stamps(&asset_path(STAMPS));
 
 	// Draw grid lines
 	for i in 0..=GRID_SIZE {
@@ -72,3 +235,541 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
 		));
 	}
 }
+
+/// Loads every stamp in the directory in file name order, skipping those that fail to load.
+fn load_stamps(directory: &Path) -> Vec<Stamp> {
+	let Ok(entries) = std::fs::read_dir(directory) else {
+		return Vec::new();
+	};
+	let mut paths: Vec<_> = entries
+		.filter_map(|entry| Some(entry.ok()?.path()))
+		.filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+		.collect();
+	paths.sort();
+
+	paths
+		.into_iter()
+		.filter_map(|path| {
+			Stamp::load(&path).map_err(|error| warn!("{}: {error}", path.display())).ok()
+		})
+		.collect()
+}
+
+/// Regenerates the world with a new seed when R is pressed.
+fn reseed_world(keys: Res<ButtonInput<KeyCode>>, mut config: ResMut<WorldGenConfig>) {
+	if keys.just_pressed(KeyCode::KeyR) {
+		config.seed = config.seed.wrapping_add(1);
+	}
+}
+
+/// Cycles through the physical, political and debug map when P is pressed.
+fn toggle_view(keys: Res<ButtonInput<KeyCode>>, mut view: ResMut<MapView>) {
+	if keys.just_pressed(KeyCode::KeyP) {
+		*view = match *view {
+			MapView::Physical => MapView::Political,
+			MapView::Political => MapView::Debug,
+			MapView::Debug => MapView::Physical,
+		};
+	}
+}
+
+/// Moves on to the next season when T is pressed.
+fn cycle_season(keys: Res<ButtonInput<KeyCode>>, mut season: ResMut<Season>) {
+	if keys.just_pressed(KeyCode::KeyT) {
+		*season = season.next();
+		info!("{:?}", *season);
+	}
+}
+
+/// Starts or pauses the simulation when Space is pressed, and fast-forwards it by a century when
+/// F is pressed.
+fn control_simulation(keys: Res<ButtonInput<KeyCode>>, mut clock: ResMut<SimulationClock>) {
+	if keys.just_pressed(KeyCode::Space) {
+		clock.running = !clock.running;
+	}
+	if keys.just_pressed(KeyCode::KeyF) {
+		clock.fast_forward(100);
+	}
+}
+
+/// Logs the distribution report of the world when S is pressed, including whatever the
+/// simulation, disasters and editor changed since it was generated.
+fn log_stats(keys: Res<ButtonInput<KeyCode>>, layers: LayerResources) {
+	if keys.just_pressed(KeyCode::KeyS) {
+		info!("{}", WorldStats::of_resources(&layers));
+	}
+}
+
+/// Renders the generated layers from bottom to top for the current [MapView] and [Season].
+///
+/// The whole map is drawn anew when the world, the view or the season changes, while cells
+/// changed in between, e.g. by the simulation, are redrawn alone.
+#[allow(clippy::too_many_arguments)]
+fn render_world(
+	mut commands: Commands,
+	mut generated: EventReader<WorldGenerated>,
+	mut changed: EventReader<LayerChanged>,
+	view: Res<MapView>,
+	overlays: Query<Entity, With<LayerSprite>>,
+	mut sprites: ResMut<MapSprites>,
+	glyph_atlas: Res<GlyphAtlas>,
+	layers: MapLayers,
+) {
+	let changes: Vec<_> = changed.read().collect();
+	let season_changed = layers.seasonal.as_ref().is_some_and(|seasonal| seasonal.is_changed());
+	// Borders outline whole territories, so they are only ever drawn whole.
+	let bordered = *view == MapView::Political
+		&& changes.iter().any(|change| change.kind == LayerKind::Territory);
+	let regenerated = generated.read().last().is_some();
+	let kinds = view.layers();
+	// Layers stay below the overlays, which are drawn at the depth of the world.
+	let depth = |index: usize| (index as f32 - kinds.len() as f32) * 0.01;
+
+	if regenerated || view.is_changed() || season_changed || bordered {
+		for entity in &overlays {
+			commands.entity(entity).despawn();
+		}
+		for sprites in sprites.0.values_mut() {
+			sprites.clear(&mut commands);
+		}
+		for (index, kind) in kinds.iter().enumerate() {
+			let sprites = sprites.0.entry(*kind).or_default();
+			layers.draw(&mut commands, *kind, None, depth(index), &glyph_atlas, sprites);
+		}
+		layers.draw_overlays(&mut commands, *view);
+		return;
+	}
+
+	// Portals are linked anew as the special layer changes, and their links with them.
+	if *view == MapView::Debug && changes.iter().any(|change| change.kind == LayerKind::Special) {
+		for entity in &overlays {
+			commands.entity(entity).despawn();
+		}
+		layers.draw_overlays(&mut commands, *view);
+	}
+	for change in changes {
+		if let Some(index) = kinds.iter().position(|kind| *kind == change.kind) {
+			let sprites = sprites.0.entry(change.kind).or_default();
+			layers.draw(
+				&mut commands,
+				change.kind,
+				Some(&change.cells),
+				depth(index),
+				&glyph_atlas,
+				sprites,
+			);
+		}
+	}
+}
+
+/// The layers of the world the map is drawn from.
+#[derive(SystemParam)]
+struct MapLayers<'w> {
+	water: Option<Res<'w, Layer<WaterType>>>,
+	terrain: Option<Res<'w, Layer<TerrainFeature>>>,
+	biome: Option<Res<'w, Layer<Biome>>>,
+	detail: Option<Res<'w, Layer<TerrainDetail>>>,
+	flora: Option<Res<'w, Layer<Flora>>>,
+	mineral: Option<Res<'w, Layer<Mineral>>>,
+	urban: Option<Res<'w, Layer<Urban>>>,
+	special: Option<Res<'w, Layer<Special>>>,
+	territory: Option<Res<'w, Layer<TerritoryId>>>,
+	portals: Option<Res<'w, PortalNetwork>>,
+	seasonal: Option<Res<'w, SeasonalLayers>>,
+}
+
+impl MapLayers<'_> {
+	/// Draws the given cells of the layer of the given kind, or all of them, above the layers of
+	/// lower depth.
+	///
+	/// The layers that change with the season are drawn as they are in the current one.
+	fn draw(
+		&self,
+		commands: &mut Commands,
+		kind: LayerKind,
+		cells: Option<&[GridPosition]>,
+		depth: f32,
+		atlas: &GlyphAtlas,
+		sprites: &mut CellSprites,
+	) {
+		let season = self.seasonal.as_ref().map_or(Season::default(), |seasonal| seasonal.season);
+		let water = self
+			.seasonal
+			.as_deref()
+			.map(|seasonal| &seasonal.water)
+			.or(self.water.as_deref());
+		let detail = self
+			.seasonal
+			.as_deref()
+			.map(|seasonal| &seasonal.detail)
+			.or(self.detail.as_deref());
+		let target = CellTarget { commands, cells, depth, sprites };
+
+		match kind {
+			LayerKind::Water => target.draw(water, LayerRender::render),
+			LayerKind::Terrain => target.draw(self.terrain.as_deref(), LayerRender::render),
+			LayerKind::Biome => target.draw(self.biome.as_deref(), LayerRender::render),
+			LayerKind::Detail => target.draw(detail, LayerRender::render),
+			LayerKind::Flora => target.draw(self.flora.as_deref(), |flora, commands, cell| {
+				flora.render_glyph_colored(commands, cell, atlas, season.flora_color(*flora))
+			}),
+			LayerKind::Mineral => target.draw(self.mineral.as_deref(), |value, commands, cell| {
+				value.render_glyph(commands, cell, atlas)
+			}),
+			LayerKind::Urban => target.draw(self.urban.as_deref(), |value, commands, cell| {
+				value.render_glyph(commands, cell, atlas)
+			}),
+			LayerKind::Special => target.draw(self.special.as_deref(), |value, commands, cell| {
+				value.render_glyph(commands, cell, atlas)
+			}),
+			LayerKind::Territory => target.draw(self.territory.as_deref(), LayerRender::render),
+		}
+	}
+
+	/// Draws what the view shows over its layers as a whole, like borders.
+	fn draw_overlays(&self, commands: &mut Commands, view: MapView) {
+		let overlays = match view {
+			MapView::Physical => Vec::new(),
+			MapView::Political => self
+				.territory
+				.as_ref()
+				.map(|territory| territory.render_borders(commands, Color::BLACK))
+				.unwrap_or_default(),
+			MapView::Debug => self
+				.portals
+				.as_ref()
+				.map(|portals| portals.render_links(commands, Color::srgb(0.9, 0.2, 0.9)))
+				.unwrap_or_default(),
+		};
+
+		for entity in overlays {
+			commands.entity(entity).insert(LayerSprite);
+		}
+	}
+}
+
+/// Which cells of a layer to draw, and where.
+struct CellTarget<'a, 'w, 's> {
+	commands: &'a mut Commands<'w, 's>,
+	/// The cells to draw, or `None` to draw the whole layer.
+	cells: Option<&'a [GridPosition]>,
+	/// How far above the background to draw the cells.
+	depth: f32,
+	sprites: &'a mut CellSprites,
+}
+
+impl CellTarget<'_, '_, '_> {
+	/// Draws the cells of the layer with `draw`, or clears them if the layer is disabled.
+	fn draw<T: LayerValue>(
+		self,
+		layer: Option<&Layer<T>>,
+		draw: impl Fn(&T, &mut Commands, &WorldCell) -> Option<Entity>,
+	) {
+		let Some(layer) = layer else {
+			self.sprites.clear(self.commands);
+			return;
+		};
+		let depth = self.depth;
+		let draw = |value: &T, commands: &mut Commands, cell: &WorldCell| {
+			let entity = draw(value, commands, cell)?;
+			let mut transform = cell.transform();
+			transform.translation.z = depth;
+			commands.entity(entity).insert(transform);
+			Some(entity)
+		};
+
+		match self.cells {
+			Some(cells) => layer.redraw(self.commands, self.sprites, cells.iter().copied(), draw),
+			None => layer.draw(self.commands, self.sprites, draw),
+		}
+	}
+}
+
+/// Zooms the camera with the mouse wheel.
+fn zoom_camera(
+	mut scroll: EventReader<MouseWheel>,
+	mut projections: Query<&mut OrthographicProjection, With<Camera2d>>,
+) {
+	let delta: f32 = scroll.read().map(|event| event.y).sum();
+	if delta == 0.0 {
+		return;
+	}
+
+	for mut projection in &mut projections {
+		projection.scale = (projection.scale * 0.9f32.powf(delta)).clamp(0.1, 8.0);
+	}
+}
+
+/// Opens the interior of the dungeon under the cursor when it is clicked, closing any open
+/// interior on the next click, on Escape or when the world is regenerated.
+#[allow(clippy::too_many_arguments)]
+fn open_dungeon(
+	mut commands: Commands,
+	mut generated: EventReader<WorldGenerated>,
+	buttons: Res<ButtonInput<MouseButton>>,
+	keys: Res<ButtonInput<KeyCode>>,
+	windows: Query<&Window>,
+	cameras: Query<(&Camera, &GlobalTransform, &OrthographicProjection)>,
+	views: Query<Entity, With<DungeonView>>,
+	dungeons: Option<ResMut<Dungeons>>,
+	editor: Res<Editor>,
+) {
+	// Clicks paint the map while the editor is on.
+	let clicked = buttons.just_pressed(MouseButton::Left) && !editor.enabled;
+	let regenerated = generated.read().last().is_some();
+	if !views.is_empty() {
+		if clicked || regenerated || keys.just_pressed(KeyCode::Escape) {
+			for entity in &views {
+				commands.entity(entity).despawn_recursive();
+			}
+		}
+		return;
+	}
+
+	let (Some(mut dungeons), true) = (dungeons, clicked) else {
+		return;
+	};
+	let (Ok(window), Ok((camera, camera_transform, projection))) =
+		(windows.get_single(), cameras.get_single())
+	else {
+		return;
+	};
+	let Some(position) = cursor_position(window, camera, camera_transform) else {
+		return;
+	};
+	let Some(interior) = dungeons.interior(position) else {
+		return;
+	};
+
+	// Draw the interior over the middle of the screen, on a backdrop of solid rock.
+	let tiles = interior.render(&mut commands);
+	let side = (1i64 << interior.world_size()) as f32;
+	let backdrop = commands
+		.spawn((
+			Sprite {
+				color: DungeonTile::Wall.get_color(),
+				custom_size: Some(Vec2::splat(side)),
+				..default()
+			},
+			Transform::from_xyz(0.0, 0.0, -0.1),
+		))
+		.id();
+	let center = camera_transform.translation().truncate();
+	commands
+		.spawn((
+			Transform::from_translation(center.extend(20.0))
+				.with_scale(Vec3::splat(projection.scale * 6.0)),
+			Visibility::default(),
+			DungeonView,
+		))
+		.add_child(backdrop)
+		.add_children(&tiles);
+}
+
+/// The world position under the cursor, if it is over the window.
+fn cursor_position(
+	window: &Window,
+	camera: &Camera,
+	camera_transform: &GlobalTransform,
+) -> Option<WorldPosition> {
+	let cursor = window
+		.cursor_position()
+		.and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor).ok())?;
+	Some(WorldPosition::new(cursor.x.floor() as i64, cursor.y.floor() as i64))
+}
+
+/// Turns the editor on or off when E is pressed, and while it is on picks what it paints with:
+/// Tab cycles the layer, the brackets the value, 1 to 3 the brush, fill and rectangle tools,
+/// Q the brush shape and plus and minus the brush size.
+fn control_editor(
+	keys: Res<ButtonInput<KeyCode>>,
+	mut editor: ResMut<Editor>,
+	layers: LayerResources,
+) {
+	if keys.just_pressed(KeyCode::KeyE) {
+		editor.enabled = !editor.enabled;
+		info!("Editor {}", if editor.enabled { "on" } else { "off" });
+	}
+	if !editor.enabled {
+		return;
+	}
+
+	let palette = layers.palette(editor.kind);
+	let before = (editor.kind, editor.variant, editor.tool, editor.brush);
+	if keys.just_pressed(KeyCode::Tab) {
+		let index = LayerKind::ALL.iter().position(|kind| *kind == editor.kind).unwrap_or(0);
+		editor.kind = LayerKind::ALL[(index + 1) % LayerKind::ALL.len()];
+		editor.variant = 0;
+	}
+	let variants = palette.len().max(1);
+	if keys.just_pressed(KeyCode::BracketRight) {
+		editor.variant = (editor.variant + 1) % variants;
+	}
+	if keys.just_pressed(KeyCode::BracketLeft) {
+		editor.variant = (editor.variant + variants - 1) % variants;
+	}
+	for (key, tool) in [
+		(KeyCode::Digit1, Tool::Brush),
+		(KeyCode::Digit2, Tool::Fill),
+		(KeyCode::Digit3, Tool::Rectangle),
+	] {
+		if keys.just_pressed(key) {
+			editor.tool = tool;
+		}
+	}
+	if keys.just_pressed(KeyCode::KeyQ) {
+		editor.brush.shape = match editor.brush.shape {
+			BrushShape::Round => BrushShape::Square,
+			BrushShape::Square => BrushShape::Round,
+		};
+	}
+	if keys.just_pressed(KeyCode::Equal) {
+		editor.brush.radius += 1;
+	}
+	if keys.just_pressed(KeyCode::Minus) {
+		editor.brush.radius = (editor.brush.radius - 1).max(0);
+	}
+
+	if before != (editor.kind, editor.variant, editor.tool, editor.brush) {
+		let palette = layers.palette(editor.kind);
+		match palette.get(editor.variant) {
+			Some(value) => info!("Painting {value:?} with {:?} {:?}", editor.tool, editor.brush),
+			None => info!("The {:?} layer is disabled", editor.kind),
+		}
+	}
+}
+
+/// Paints the map under the cursor with the tool and value picked in the [Editor], recording
+/// every change in the [ChangeLog].
+///
+/// Everything a brush paints until it is lifted is undone at once.
+#[allow(clippy::too_many_arguments)]
+fn paint_map(
+	mut editor: ResMut<Editor>,
+	buttons: Res<ButtonInput<MouseButton>>,
+	windows: Query<&Window>,
+	cameras: Query<(&Camera, &GlobalTransform)>,
+	mut layers: LayerResources,
+	mut log: ResMut<ChangeLog>,
+	mut history: ResMut<History>,
+) {
+	if buttons.just_released(MouseButton::Left) {
+		history.end_stroke();
+	}
+	if !editor.enabled {
+		return;
+	}
+	if editor.tool == Tool::Brush && buttons.just_pressed(MouseButton::Left) {
+		history.begin_stroke();
+	}
+	let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single())
+	else {
+		return;
+	};
+	let Some(position) = cursor_position(window, camera, camera_transform) else {
+		return;
+	};
+	let Some(value) = layers.palette(editor.kind).get(editor.variant).copied() else {
+		return;
+	};
+
+	let edit = match editor.tool {
+		Tool::Brush if buttons.pressed(MouseButton::Left) => {
+			Edit::Paint { brush: editor.brush, center: position }
+		}
+		Tool::Fill if buttons.just_pressed(MouseButton::Left) => Edit::Fill(position),
+		Tool::Rectangle if buttons.just_pressed(MouseButton::Left) => {
+			editor.anchor = Some(position);
+			return;
+		}
+		Tool::Rectangle if buttons.just_released(MouseButton::Left) => {
+			let Some(anchor) = editor.anchor.take() else {
+				return;
+			};
+			Edit::Rectangle(anchor, position)
+		}
+		_ => return,
+	};
+	layers.edit(&edit, value, &mut log);
+}
+
+/// Undoes the latest change to the map when Ctrl+Z is pressed, and redoes the latest undone one
+/// when Ctrl+Y or Ctrl+Shift+Z is.
+fn undo_changes(
+	keys: Res<ButtonInput<KeyCode>>,
+	mut history: ResMut<History>,
+	mut layers: LayerResources,
+	mut log: ResMut<ChangeLog>,
+) {
+	if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+		return;
+	}
+
+	let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+	let undo = keys.just_pressed(KeyCode::KeyZ) && !shift;
+	let redo = keys.just_pressed(KeyCode::KeyY) || keys.just_pressed(KeyCode::KeyZ) && shift;
+	if undo && !history.undo(&mut layers, &mut log) {
+		info!("Nothing to undo");
+	}
+	if redo && !history.redo(&mut layers, &mut log) {
+		info!("Nothing to redo");
+	}
+}
+
+/// Spawns a label for every named place, replacing the labels of any previous world or
+/// settlements since built or ruined.
+fn render_labels(
+	mut commands: Commands,
+	mut generated: EventReader<WorldGenerated>,
+	labels: Query<Entity, With<PlaceLabel>>,
+	names: Option<Res<WorldNames>>,
+) {
+	// Names change with the world, and as settlements are built and ruined.
+	let renamed = names.as_ref().is_some_and(|names| names.is_changed());
+	if generated.read().last().is_none() && !renamed {
+		return;
+	}
+
+	for entity in &labels {
+		commands.entity(entity).despawn();
+	}
+
+	let Some(names) = names else {
+		return;
+	};
+	for place in names.iter() {
+		// Large places read best zoomed out, settlements only once zoomed in on them.
+		let (font_size, color, zoom) = match place.kind {
+			PlaceKind::Landmass => (24.0, Color::WHITE, 0.5..=8.0),
+			PlaceKind::Ocean => (22.0, Color::srgb(0.7, 0.85, 1.0), 0.5..=8.0),
+			PlaceKind::Lake => (16.0, Color::srgb(0.7, 0.85, 1.0), 0.2..=2.0),
+			PlaceKind::City | PlaceKind::Port => (14.0, Color::srgb(1.0, 0.95, 0.8), 0.1..=1.0),
+		};
+		commands.spawn((
+			Text2d::new(place.name.clone()),
+			TextFont { font_size, ..default() },
+			TextColor(color),
+			Transform::from_xyz(place.position.x as f32, place.position.y as f32, 10.0),
+			PlaceLabel { zoom },
+		));
+	}
+}
+
+/// Shows the labels meant for the current zoom level, keeping them the same size on screen.
+fn show_labels(
+	projections: Query<&OrthographicProjection, With<Camera2d>>,
+	mut labels: Query<(&PlaceLabel, &mut Visibility, &mut Transform)>,
+) {
+	let Ok(projection) = projections.get_single() else {
+		return;
+	};
+
+	for (label, mut visibility, mut transform) in &mut labels {
+		*visibility = if label.zoom.contains(&projection.scale) {
+			Visibility::Inherited
+		} else {
+			Visibility::Hidden
+		};
+		transform.scale = Vec3::splat(projection.scale);
+	}
+}
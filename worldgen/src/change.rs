@@ -0,0 +1,352 @@
+use crate::layer::layers::biome::Biome;
+use crate::layer::layers::detail::TerrainDetail;
+use crate::layer::layers::flora::Flora;
+use crate::layer::layers::mineral::Mineral;
+use crate::layer::layers::special::Special;
+use crate::layer::layers::terrain::TerrainFeature;
+use crate::layer::layers::territory::TerritoryId;
+use crate::layer::layers::urban::Urban;
+use crate::layer::layers::water::WaterType;
+use crate::layer::{Layer, LayerValue, WorldPosition};
+use crate::world::{LayerKind, WorldLayers};
+#[cfg(feature = "bevy")]
+use bevy::prelude::*;
+
+/// A value of one of the layers of a world, which knows which layer that is.
+pub trait WorldValue: LayerValue {
+	const KIND: LayerKind;
+
+	fn into_cell(self) -> CellValue;
+
+	/// Unwraps the value, or returns `None` if the cell value belongs to another layer.
+	fn from_cell(value: CellValue) -> Option<Self>;
+}
+
+macro_rules! cell_values {
+	($($kind:ident($value:ty, $field:ident);)*) => {
+		/// The value of a cell in any of the layers of a world.
+		#[derive(Clone, Copy, Debug, PartialEq)]
+		#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+		pub enum CellValue {
+			$($kind($value),)*
+		}
+
+		impl CellValue {
+			/// The layer the value belongs to.
+			pub fn kind(&self) -> LayerKind {
+				match self {
+					$(CellValue::$kind(_) => LayerKind::$kind,)*
+				}
+			}
+		}
+
+		$(
+			impl WorldValue for $value {
+				const KIND: LayerKind = LayerKind::$kind;
+
+				fn into_cell(self) -> CellValue {
+					CellValue::$kind(self)
+				}
+
+				fn from_cell(value: CellValue) -> Option<Self> {
+					match value {
+						CellValue::$kind(value) => Some(value),
+						_ => None,
+					}
+				}
+			}
+		)*
+
+		impl WorldLayers {
+			/// Gets the value of the cell covering the position in the given layer.
+			pub fn get_cell(&self, kind: LayerKind, position: WorldPosition) -> CellValue {
+				match kind {
+					$(LayerKind::$kind => CellValue::$kind(self.$field.get(position)),)*
+				}
+			}
+
+			/// Sets the cell covering the position in the layer of the value, recording the change
+			/// in the log.
+			pub fn set_cell(
+				&mut self,
+				position: WorldPosition,
+				value: CellValue,
+				log: &mut ChangeLog,
+			) -> bool {
+				match value {
+					$(CellValue::$kind(value) => log.set(&mut self.$field, position, value),)*
+				}
+			}
+		}
+
+		impl CellLayers for WorldLayers {
+			fn set_cell(
+				&mut self,
+				position: WorldPosition,
+				value: CellValue,
+				log: &mut ChangeLog,
+			) -> bool {
+				WorldLayers::set_cell(self, position, value, log)
+			}
+		}
+
+		/// The layer resources of a world, to get and set their cells by [CellValue] from a
+		/// system.
+		///
+		/// Disabled layers are missing.
+		#[cfg(feature = "bevy")]
+		#[derive(bevy::ecs::system::SystemParam)]
+		pub struct LayerResources<'w> {
+			$(pub $field: Option<ResMut<'w, Layer<$value>>>,)*
+		}
+
+		#[cfg(feature = "bevy")]
+		impl LayerResources<'_> {
+			/// Gets the value of the cell covering the position in the given layer, or `None` if
+			/// the layer is disabled.
+			pub fn get_cell(&self, kind: LayerKind, position: WorldPosition) -> Option<CellValue> {
+				match kind {
+					$(LayerKind::$kind => {
+						self.$field.as_ref().map(|layer| CellValue::$kind(layer.get(position)))
+					})*
+				}
+			}
+
+			/// Sets the cell covering the position in the layer of the value, recording the change
+			/// in the log.
+			///
+			/// Returns whether the cell changed, which it never does in a disabled layer.
+			pub fn set_cell(
+				&mut self,
+				position: WorldPosition,
+				value: CellValue,
+				log: &mut ChangeLog,
+			) -> bool {
+				match value {
+					$(CellValue::$kind(value) => self
+						.$field
+						.as_deref_mut()
+						.is_some_and(|layer| log.set(layer, position, value)),)*
+				}
+			}
+
+			/// Sets every cell to the value it has after the changes, skipping disabled layers.
+			pub fn apply_changes(&mut self, changes: &[CellChange], log: &mut ChangeLog) {
+				CellLayers::apply_changes(self, changes, log);
+			}
+		}
+
+		#[cfg(feature = "bevy")]
+		impl CellLayers for LayerResources<'_> {
+			fn set_cell(
+				&mut self,
+				position: WorldPosition,
+				value: CellValue,
+				log: &mut ChangeLog,
+			) -> bool {
+				LayerResources::set_cell(self, position, value, log)
+			}
+		}
+	};
+}
+
+cell_values! {
+	Water(WaterType, water);
+	Terrain(TerrainFeature, terrain);
+	Biome(Biome, biome);
+	Detail(TerrainDetail, detail);
+	Flora(Flora, flora);
+	Mineral(Mineral, mineral);
+	Urban(Urban, urban);
+	Special(Special, special);
+	Territory(TerritoryId, territory);
+}
+
+/// The layers of a world, whose cells can be set by [CellValue], like a [WorldLayers] or its
+/// layer resources.
+pub trait CellLayers {
+	/// Sets the cell covering the position in the layer of the value, recording the change in
+	/// the log.
+	///
+	/// Returns whether the cell changed.
+	fn set_cell(&mut self, position: WorldPosition, value: CellValue, log: &mut ChangeLog) -> bool;
+
+	/// Sets every cell to the value it has after the changes.
+	fn apply_changes(&mut self, changes: &[CellChange], log: &mut ChangeLog) {
+		for change in changes {
+			self.set_cell(change.position, change.after, log);
+		}
+	}
+}
+
+impl WorldLayers {
+	/// Sets every cell to the value it has after the changes, e.g. to replay changes saved from
+	/// another copy of the world.
+	pub fn apply_changes(&mut self, changes: &[CellChange], log: &mut ChangeLog) {
+		CellLayers::apply_changes(self, changes, log);
+	}
+}
+
+/// A change of one cell of a layer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CellChange {
+	/// The lowest corner of the changed cell.
+	pub position: WorldPosition,
+	pub before: CellValue,
+	pub after: CellValue,
+}
+
+impl CellChange {
+	/// The layer the change was made to.
+	pub fn kind(&self) -> LayerKind {
+		self.after.kind()
+	}
+
+	/// The change undoing this one.
+	pub fn inverse(&self) -> Self {
+		Self { position: self.position, before: self.after, after: self.before }
+	}
+}
+
+/// Records the changes made to layers after they were generated.
+///
+/// Everything rewriting a layer goes through [ChangeLog::set], so whatever mirrors the layers,
+/// like sprites, can catch up on the recorded changes instead of starting over. The
+/// [crate::history::History] takes the changes as it records them, so the log only holds the
+/// changes made since; save files should store [crate::history::History::to_log] instead.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(Resource))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChangeLog {
+	changes: Vec<CellChange>,
+}
+
+impl ChangeLog {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the cell covering the position, recording the change unless the cell already holds
+	/// the value.
+	///
+	/// Returns whether the cell changed.
+	pub fn set<T: WorldValue>(
+		&mut self,
+		layer: &mut Layer<T>,
+		position: WorldPosition,
+		value: T,
+	) -> bool {
+		let cell = layer.get_grid_position(position);
+		let before = layer.get_grid(cell);
+		if before == value {
+			return false;
+		}
+
+		layer.set_grid(cell, value);
+		self.changes.push(CellChange {
+			position: cell.into(),
+			before: before.into_cell(),
+			after: value.into_cell(),
+		});
+		true
+	}
+
+	/// The recorded changes, oldest first.
+	pub fn changes(&self) -> &[CellChange] {
+		&self.changes
+	}
+
+	/// Takes the recorded changes, leaving the log empty.
+	pub fn take(&mut self) -> Vec<CellChange> {
+		std::mem::take(&mut self.changes)
+	}
+
+	pub fn len(&self) -> usize {
+		self.changes.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.changes.is_empty()
+	}
+
+	/// Serializes the log as JSON, e.g. to save the changes along with the world's config.
+	#[cfg(feature = "serde")]
+	pub fn to_json(&self) -> String {
+		serde_json::to_string(self).expect("change logs always serialize")
+	}
+
+	#[cfg(feature = "serde")]
+	pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+		serde_json::from_str(json)
+	}
+}
+
+impl FromIterator<CellChange> for ChangeLog {
+	fn from_iter<I: IntoIterator<Item = CellChange>>(changes: I) -> Self {
+		Self { changes: changes.into_iter().collect() }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::world::WorldGenConfig;
+
+	#[test]
+	fn only_actual_changes_are_recorded() {
+		let mut layer = Layer::new_base32(2, 4);
+		let mut log = ChangeLog::new();
+
+		assert!(log.set(&mut layer, WorldPosition::new(5, -3), Flora::Tree));
+		assert!(!log.set(&mut layer, WorldPosition::new(6, -2), Flora::Tree));
+		assert!(!log.set(&mut layer, WorldPosition::new(0, 0), Flora::None));
+
+		assert_eq!(
+			log.changes(),
+			&[CellChange {
+				position: WorldPosition::new(4, -4),
+				before: CellValue::Flora(Flora::None),
+				after: CellValue::Flora(Flora::Tree),
+			}]
+		);
+		assert_eq!(log.changes()[0].kind(), LayerKind::Flora);
+		assert_eq!(Flora::from_cell(log.changes()[0].after), Some(Flora::Tree));
+		assert_eq!(Urban::from_cell(log.changes()[0].after), None);
+	}
+
+	#[test]
+	fn changes_replay_onto_a_copy_and_invert() {
+		let original = WorldLayers::generate(&WorldGenConfig::default());
+		let mut world = original.clone();
+		let mut log = ChangeLog::new();
+		let position = WorldPosition::new(100, -50);
+		world.set_cell(position, CellValue::Urban(Urban::Temple), &mut log);
+		world.set_cell(position, CellValue::Detail(TerrainDetail::Rock), &mut log);
+		world.set_cell(WorldPosition::new(0, 0), CellValue::Water(WaterType::Lake), &mut log);
+
+		let mut copy = original.clone();
+		copy.apply_changes(log.changes(), &mut ChangeLog::new());
+		for change in log.changes() {
+			assert_eq!(copy.get_cell(change.kind(), change.position), change.after);
+		}
+
+		let inverse: Vec<_> = log.changes().iter().rev().map(CellChange::inverse).collect();
+		copy.apply_changes(&inverse, &mut ChangeLog::new());
+		for kind in LayerKind::ALL {
+			for position in original.positions(kind) {
+				assert_eq!(copy.get_cell(kind, position), original.get_cell(kind, position));
+			}
+		}
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn logs_round_trip_through_json() {
+		let mut log = ChangeLog::new();
+		log.set(&mut Layer::new_base32(0, 4), WorldPosition::new(-1, 2), Urban::Ruin);
+		log.set(&mut Layer::new_base32(0, 4), WorldPosition::new(3, 3), TerritoryId(4));
+
+		assert_eq!(ChangeLog::from_json(&log.to_json()).unwrap(), log);
+	}
+}
@@ -0,0 +1,396 @@
+use crate::change::ChangeLog;
+use crate::layer::base::{streams, NoiseGenerator};
+use crate::layer::layers::biome::Biome;
+use crate::layer::layers::detail::TerrainDetail;
+use crate::layer::layers::flora::Flora;
+use crate::layer::layers::special::Special;
+use crate::layer::layers::terrain::TerrainFeature;
+use crate::layer::layers::urban::Urban;
+use crate::layer::layers::water::WaterType;
+use crate::layer::{Layer, LayerValue, Neighborhood, WorldPosition};
+use crate::world::WorldLayers;
+#[cfg(feature = "bevy")]
+use bevy::prelude::*;
+use rand::prelude::*;
+use std::collections::{HashSet, VecDeque};
+
+/// The fewest years between two eruptions of a volcano.
+const MIN_ERUPTION_PERIOD: u64 = 30;
+
+/// The chance of a wildfire breaking out in any one year.
+const WILDFIRE_CHANCE: f64 = 0.25;
+
+/// The layers that disasters rewrite, along with the layers they only read.
+///
+/// Borrowing them one by one lets disasters strike both a [WorldLayers] and layers held
+/// elsewhere, like the resources of the `WorldGenPlugin`.
+pub struct Surface<'a> {
+	pub water: &'a Layer<WaterType>,
+	pub terrain: &'a Layer<TerrainFeature>,
+	pub biome: &'a Layer<Biome>,
+	pub flora: &'a mut Layer<Flora>,
+	pub detail: &'a mut Layer<TerrainDetail>,
+	pub urban: &'a mut Layer<Urban>,
+}
+
+impl WorldLayers {
+	/// Borrows the layers disasters rewrite.
+	pub fn surface(&mut self) -> Surface<'_> {
+		Surface {
+			water: &self.water,
+			terrain: &self.terrain,
+			biome: &self.biome,
+			flora: &mut self.flora,
+			detail: &mut self.detail,
+			urban: &mut self.urban,
+		}
+	}
+}
+
+/// A volcano erupting, burying the land around it under bare rock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Eruption {
+	pub volcano: WorldPosition,
+	/// How far the lava flows, in world units.
+	pub radius: i64,
+}
+
+impl Eruption {
+	/// Clears the flora and settlements of every land cell within the radius and turns its
+	/// detail to rock.
+	pub fn apply(&self, surface: &mut Surface, log: &mut ChangeLog) {
+		for position in cells_within(surface.flora, self.volcano, self.radius) {
+			log.set(surface.flora, position, Flora::None);
+		}
+		for position in cells_within(surface.urban, self.volcano, self.radius) {
+			log.set(surface.urban, position, Urban::None);
+		}
+		for position in cells_within(surface.detail, self.volcano, self.radius) {
+			if !surface.water.get(position).is_water() {
+				log.set(surface.detail, position, TerrainDetail::Rock);
+			}
+		}
+	}
+}
+
+/// A wildfire spreading from one burning plant through the dry woods around it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Wildfire {
+	pub origin: WorldPosition,
+	/// The direction the wind blows towards, as a step to a neighboring cell.
+	pub wind: (i64, i64),
+	/// Seeds where the fire happens to spread, so the same fire always burns the same cells.
+	pub seed: u64,
+}
+
+impl Wildfire {
+	/// Whether fire spreads through the plant, which takes trees or bushes in a dry biome.
+	pub fn is_flammable(flora: Flora, biome: Biome) -> bool {
+		matches!(flora, Flora::Tree | Flora::Bush)
+			&& matches!(biome, Biome::Desert | Biome::Grassland)
+	}
+
+	/// The chance of fire leaping one step in the given direction, which is highest downwind
+	/// and lowest against the wind.
+	pub fn spread_chance(&self, (x, y): (i64, i64)) -> f64 {
+		let (wind_x, wind_y) = self.wind;
+		let alignment = (x * wind_x + y * wind_y) as f64
+			/ (((x * x + y * y) * (wind_x * wind_x + wind_y * wind_y)) as f64).sqrt().max(1.0);
+		0.5 + 0.4 * alignment
+	}
+
+	/// Burns the plant at the origin and spreads to neighboring flammable plants, clearing
+	/// every plant it burns.
+	pub fn apply(&self, surface: &mut Surface, log: &mut ChangeLog) {
+		let cell_size = surface.flora.scale();
+		let origin = surface.flora.get_grid_position(self.origin).into();
+		let flammable = |flora: &Layer<Flora>, position: WorldPosition| {
+			flora.contains(position)
+				&& Self::is_flammable(flora.get(position), surface.biome.get(position))
+		};
+		if !flammable(surface.flora, origin) {
+			return;
+		}
+
+		let mut rng = StdRng::seed_from_u64(self.seed);
+		let mut burnt = HashSet::from([origin]);
+		let mut burning = VecDeque::from([origin]);
+		while let Some(position) = burning.pop_front() {
+			for offset in Neighborhood::Moore.offsets() {
+				let neighbor = WorldPosition::new(
+					position.x + offset.0 * cell_size,
+					position.y + offset.1 * cell_size,
+				);
+				if !burnt.contains(&neighbor)
+					&& flammable(surface.flora, neighbor)
+					&& rng.gen_bool(self.spread_chance(offset))
+				{
+					burnt.insert(neighbor);
+					burning.push_back(neighbor);
+				}
+			}
+		}
+
+		// Clearing the plants last keeps them flammable while the fire spreads.
+		let mut burnt: Vec<_> = burnt.into_iter().collect();
+		burnt.sort_unstable_by_key(|position| (position.y, position.x));
+		for position in burnt {
+			log.set(surface.flora, position, Flora::None);
+		}
+	}
+}
+
+/// A disaster striking a world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Disaster {
+	Eruption(Eruption),
+	Wildfire(Wildfire),
+}
+
+impl Disaster {
+	/// Rewrites the layers struck by the disaster, recording every change in the log.
+	pub fn apply(&self, surface: &mut Surface, log: &mut ChangeLog) {
+		match self {
+			Disaster::Eruption(eruption) => eruption.apply(surface, log),
+			Disaster::Wildfire(wildfire) => wildfire.apply(surface, log),
+		}
+	}
+}
+
+/// A volcano and when it erupts.
+#[derive(Clone, Copy, Debug)]
+struct Volcano {
+	position: WorldPosition,
+	/// The years between two eruptions.
+	period: u64,
+	/// The year of the first eruption.
+	phase: u64,
+}
+
+/// Decides which disasters strike a world in which year.
+///
+/// Every volcano erupts periodically, each with its own period, while wildfires break out by
+/// chance with the wind blowing in a new direction every year.
+#[derive(Clone)]
+#[cfg_attr(feature = "bevy", derive(Resource))]
+pub struct Disasters {
+	noise_gen: NoiseGenerator,
+	volcanoes: Vec<Volcano>,
+	eruption_radius: i64,
+}
+
+impl Disasters {
+	/// Collects the `Special::Volcano` cells of the layer, each erupting every 30 to 80 years.
+	pub fn new(noise_gen: &NoiseGenerator, special: &Layer<Special>) -> Self {
+		let volcanoes = special
+			.positions()
+			.filter(|position| special.get(*position) == Special::Volcano)
+			.map(|position| {
+				let value = noise_gen.get_noise_value(&position, streams::DISASTER) as u64;
+				let period = MIN_ERUPTION_PERIOD + value % 51;
+				Volcano { position, period, phase: (value >> 8) % period }
+			})
+			.collect();
+		// Lava flows across a few cells of the special layer.
+		Self { noise_gen: noise_gen.clone(), volcanoes, eruption_radius: special.scale() * 8 }
+	}
+
+	/// The disasters striking in the given year, eruptions first.
+	pub fn of_year(&self, year: u64, flora: &Layer<Flora>, biome: &Layer<Biome>) -> Vec<Disaster> {
+		let mut disasters: Vec<_> = self
+			.volcanoes
+			.iter()
+			.filter(|volcano| year % volcano.period == volcano.phase)
+			.map(|volcano| {
+				Disaster::Eruption(Eruption {
+					volcano: volcano.position,
+					radius: self.eruption_radius,
+				})
+			})
+			.collect();
+
+		let seed = self
+			.noise_gen
+			.get_noise_value(&WorldPosition::new(year as i64, 0), streams::DISASTER_WILDFIRE);
+		let mut rng = StdRng::seed_from_u64(seed as u64);
+		if rng.gen_bool(WILDFIRE_CHANCE) {
+			let tinder: Vec<_> = flora
+				.positions()
+				.filter(|position| {
+					Wildfire::is_flammable(flora.get(*position), biome.get(*position))
+				})
+				.collect();
+			if let Some(origin) = tinder.choose(&mut rng) {
+				let wind =
+					*Neighborhood::Moore.offsets().choose(&mut rng).expect("there are eight winds");
+				disasters.push(Disaster::Wildfire(Wildfire {
+					origin: *origin,
+					wind,
+					seed: rng.gen(),
+				}));
+			}
+		}
+		disasters
+	}
+
+	/// Lets the disasters of the given year strike, returning them.
+	pub fn strike(&self, year: u64, surface: &mut Surface, log: &mut ChangeLog) -> Vec<Disaster> {
+		let disasters = self.of_year(year, surface.flora, surface.biome);
+		for disaster in &disasters {
+			disaster.apply(surface, log);
+		}
+		disasters
+	}
+}
+
+/// The positions of the cells of the layer whose centers lie within the radius of the center.
+fn cells_within<T: LayerValue>(
+	layer: &Layer<T>,
+	center: WorldPosition,
+	radius: i64,
+) -> Vec<WorldPosition> {
+	let cell_size = layer.scale();
+	let low = layer.get_grid_position(WorldPosition::new(center.x - radius, center.y - radius));
+	let high = layer.get_grid_position(WorldPosition::new(center.x + radius, center.y + radius));
+	let mut cells = Vec::new();
+	for y in low.y..=high.y {
+		for x in low.x..=high.x {
+			let position = WorldPosition::new(x * cell_size, y * cell_size);
+			let (dx, dy) =
+				(position.x + cell_size / 2 - center.x, position.y + cell_size / 2 - center.y);
+			if layer.contains(position) && dx * dx + dy * dy <= radius * radius {
+				cells.push(position);
+			}
+		}
+	}
+	cells
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::world::WorldGenConfig;
+
+	type Woods = (Layer<WaterType>, Layer<Biome>, Layer<Flora>, Layer<TerrainDetail>, Layer<Urban>);
+
+	/// A 32x32 world of grassland covered in trees, with a village at the origin.
+	fn woods() -> Woods {
+		let water = Layer::new_base32(0, 5);
+		let mut biome = Layer::new_base32(0, 5);
+		let mut flora = Layer::new_base32(0, 5);
+		for position in flora.positions() {
+			biome.set(position, Biome::Grassland);
+			flora.set(position, Flora::Tree);
+		}
+		let mut urban = Layer::new_base32(0, 5);
+		urban.set(WorldPosition::new(0, 0), Urban::House);
+		(water, biome, flora, Layer::new_base32(0, 5), urban)
+	}
+
+	#[test]
+	fn eruptions_bury_the_land_around_the_volcano() {
+		let (water, biome, mut flora, mut detail, mut urban) = woods();
+		let terrain = Layer::new_base32(0, 5);
+		let mut surface = Surface {
+			water: &water,
+			terrain: &terrain,
+			biome: &biome,
+			flora: &mut flora,
+			detail: &mut detail,
+			urban: &mut urban,
+		};
+		let mut log = ChangeLog::new();
+		Eruption { volcano: WorldPosition::new(2, 0), radius: 4 }.apply(&mut surface, &mut log);
+
+		assert_eq!(urban.get(WorldPosition::new(0, 0)), Urban::None);
+		assert_eq!(flora.get(WorldPosition::new(5, 0)), Flora::None);
+		assert_eq!(detail.get(WorldPosition::new(2, 3)), TerrainDetail::Rock);
+		assert_eq!(flora.get(WorldPosition::new(7, 0)), Flora::Tree);
+		assert_eq!(detail.get(WorldPosition::new(2, 6)), TerrainDetail::None);
+		// One change for every tree, rock and the house.
+		let buried = cells_within(&flora, WorldPosition::new(2, 0), 4).len();
+		assert_eq!(log.len(), buried * 2 + 1);
+	}
+
+	#[test]
+	fn wildfires_spread_with_the_wind() {
+		let (water, biome, mut flora, mut detail, mut urban) = woods();
+		// A stream of bare ground stops the fire on the west.
+		for y in -16..16 {
+			flora.set(WorldPosition::new(-10, y), Flora::None);
+		}
+		let terrain = Layer::new_base32(0, 5);
+		let mut surface = Surface {
+			water: &water,
+			terrain: &terrain,
+			biome: &biome,
+			flora: &mut flora,
+			detail: &mut detail,
+			urban: &mut urban,
+		};
+		let mut log = ChangeLog::new();
+		Wildfire { origin: WorldPosition::new(0, 0), wind: (1, 0), seed: 3 }
+			.apply(&mut surface, &mut log);
+
+		let burnt: Vec<_> = log.changes().iter().map(|change| change.position).collect();
+		let east = burnt.iter().filter(|position| position.x > 0).count();
+		let west = burnt.iter().filter(|position| position.x < 0).count();
+		assert!(east > west, "{east} cells burnt downwind, {west} upwind");
+		assert!(burnt.iter().all(|position| position.x > -10));
+		assert!(burnt.iter().all(|position| flora.get(*position) == Flora::None));
+	}
+
+	#[test]
+	fn fires_only_burn_dry_woods() {
+		assert!(Wildfire::is_flammable(Flora::Tree, Biome::Grassland));
+		assert!(Wildfire::is_flammable(Flora::Bush, Biome::Desert));
+		assert!(!Wildfire::is_flammable(Flora::Tree, Biome::Jungle));
+		assert!(!Wildfire::is_flammable(Flora::Flower, Biome::Grassland));
+
+		let fire = Wildfire { origin: WorldPosition::new(0, 0), wind: (0, 1), seed: 0 };
+		assert!(fire.spread_chance((0, 1)) > fire.spread_chance((1, 0)));
+		assert!(fire.spread_chance((1, 0)) > fire.spread_chance((0, -1)));
+	}
+
+	#[test]
+	fn volcanoes_erupt_periodically() {
+		let world = WorldLayers::generate(&WorldGenConfig { seed: 2, ..WorldGenConfig::default() });
+		let disasters = Disasters::new(&NoiseGenerator::new(2), &world.special);
+		assert!(!disasters.volcanoes.is_empty());
+
+		for volcano in &disasters.volcanoes {
+			let years: Vec<u64> = (0..200)
+				.filter(|year| {
+					disasters.of_year(*year, &world.flora, &world.biome).iter().any(|disaster| {
+						matches!(disaster, Disaster::Eruption(eruption) if eruption.volcano == volcano.position)
+					})
+				})
+				.collect();
+			assert!(years.len() >= 2);
+			assert!(years.windows(2).all(|pair| pair[1] - pair[0] == volcano.period));
+		}
+	}
+
+	#[test]
+	fn every_change_is_recorded() {
+		let original =
+			WorldLayers::generate(&WorldGenConfig { seed: 2, ..WorldGenConfig::default() });
+		let disasters = Disasters::new(&NoiseGenerator::new(2), &original.special);
+		let mut world = original.clone();
+		let mut log = ChangeLog::new();
+		let struck: usize = (0..100)
+			.map(|year| disasters.strike(year, &mut world.surface(), &mut log).len())
+			.sum();
+		assert!(struck > 0);
+		assert!(!log.is_empty());
+
+		// Replaying the log on the original world catches it up with the struck one.
+		let mut replayed = original.clone();
+		replayed.apply_changes(log.changes(), &mut ChangeLog::new());
+		for position in world.flora.positions() {
+			assert_eq!(replayed.flora.get(position), world.flora.get(position));
+			assert_eq!(replayed.detail.get(position), world.detail.get(position));
+			assert_eq!(replayed.urban.get(position), world.urban.get(position));
+		}
+	}
+}
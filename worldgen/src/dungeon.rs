@@ -0,0 +1,460 @@
+use crate::layer::base::{streams, NoiseGenerator};
+use crate::layer::layers::special::Special;
+#[cfg(feature = "bevy")]
+use crate::layer::render::LayerRender;
+use crate::layer::{GridPosition, Layer, LayerValue, Neighborhood, WorldPosition};
+#[cfg(feature = "bevy")]
+use bevy::prelude::*;
+use rand::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// The interior of a dungeon spans `1 << INTERIOR_SIZE` tiles per side.
+pub const INTERIOR_SIZE: u32 = 6;
+
+/// The smallest side of a BSP leaf, leaving room for a room and its walls.
+const MIN_LEAF: i64 = 8;
+
+/// A tile of a dungeon interior, where the default is solid rock.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DungeonTile {
+	#[default]
+	Wall,
+	Floor,
+	Door,
+	Loot,
+	/// Where travelers come down into the dungeon.
+	Entrance,
+}
+
+impl LayerValue for DungeonTile {}
+
+#[cfg(feature = "bevy")]
+impl LayerRender for DungeonTile {
+	fn render(
+		&self,
+		commands: &mut Commands,
+		world_cell: &crate::layer::WorldCell,
+	) -> Option<Entity> {
+		if *self == DungeonTile::Wall {
+			return None;
+		}
+
+		let entity = commands
+			.spawn((
+				Sprite {
+					color: self.get_color(),
+					custom_size: Some(world_cell.size()),
+					..default()
+				},
+				world_cell.transform(),
+			))
+			.id();
+		Some(entity)
+	}
+
+	fn get_color(&self) -> Color {
+		match self {
+			DungeonTile::Wall => Color::srgb(0.1, 0.1, 0.1),
+			DungeonTile::Floor => Color::srgb(0.5, 0.45, 0.4),
+			DungeonTile::Door => Color::srgb(0.5, 0.3, 0.1),
+			DungeonTile::Loot => Color::srgb(0.9, 0.8, 0.1),
+			DungeonTile::Entrance => Color::srgb(0.2, 0.8, 0.2),
+		}
+	}
+}
+
+/// How a dungeon interior is laid out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DungeonStyle {
+	/// Rectangular rooms joined by corridors, from a binary space partition.
+	Rooms,
+	/// Winding natural caves, from a cellular automaton.
+	Caves,
+}
+
+impl DungeonStyle {
+	/// The style of the dungeon at the given position, which is deterministic for a given seed and
+	/// position.
+	pub fn of(noise_gen: &NoiseGenerator, position: WorldPosition) -> Self {
+		if noise_gen.get_noise_value(&position, streams::DUNGEON_STYLE) % 3 == 0 {
+			DungeonStyle::Caves
+		} else {
+			DungeonStyle::Rooms
+		}
+	}
+}
+
+/// Generates the interior of the dungeon at the given position in the given style.
+///
+/// Interiors are centered on the origin with one unit per tile, and every open tile is
+/// reachable from the entrance.
+pub fn generate_interior(
+	noise_gen: &NoiseGenerator,
+	position: WorldPosition,
+	style: DungeonStyle,
+) -> Layer<DungeonTile> {
+	let seed = noise_gen.get_noise_value(&position, streams::DUNGEON);
+	let mut rng = StdRng::seed_from_u64(seed as u64);
+	let mut grid = Grid::new(1 << INTERIOR_SIZE);
+	match style {
+		DungeonStyle::Rooms => grid.carve_rooms(&mut rng),
+		DungeonStyle::Caves => grid.carve_caves(&mut rng),
+	}
+
+	let mut layer = Layer::new_base32(0, INTERIOR_SIZE);
+	let half = grid.side / 2;
+	for y in 0..grid.side {
+		for x in 0..grid.side {
+			layer.set(WorldPosition::new(x - half, y - half), grid.get(x, y));
+		}
+	}
+	layer
+}
+
+/// A rectangle of tiles, from its lowest corner.
+#[derive(Clone, Copy, Debug)]
+struct Rect {
+	x: i64,
+	y: i64,
+	width: i64,
+	height: i64,
+}
+
+impl Rect {
+	fn center(&self) -> (i64, i64) {
+		(self.x + self.width / 2, self.y + self.height / 2)
+	}
+
+	fn contains(&self, (x, y): (i64, i64)) -> bool {
+		(self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+	}
+}
+
+/// The tiles of an interior while it is carved out, indexed from the lowest corner.
+struct Grid {
+	side: i64,
+	tiles: Vec<DungeonTile>,
+}
+
+impl Grid {
+	fn new(side: i64) -> Self {
+		Self { side, tiles: vec![DungeonTile::Wall; (side * side) as usize] }
+	}
+
+	fn get(&self, x: i64, y: i64) -> DungeonTile {
+		if (0..self.side).contains(&x) && (0..self.side).contains(&y) {
+			self.tiles[(y * self.side + x) as usize]
+		} else {
+			DungeonTile::Wall
+		}
+	}
+
+	fn set(&mut self, x: i64, y: i64, tile: DungeonTile) {
+		self.tiles[(y * self.side + x) as usize] = tile;
+	}
+
+	/// The number of walls around a tile, counting tiles beyond the edge as walls.
+	fn walls_around(&self, x: i64, y: i64) -> usize {
+		Neighborhood::Moore
+			.offsets()
+			.into_iter()
+			.filter(|(dx, dy)| self.get(x + dx, y + dy) == DungeonTile::Wall)
+			.count()
+	}
+
+	/// Splits the interior into leaves, places a room in each and joins sibling subtrees with
+	/// corridors, so every room is reachable.
+	fn carve_rooms(&mut self, rng: &mut StdRng) {
+		let mut rooms = Vec::new();
+		let bounds = Rect { x: 1, y: 1, width: self.side - 2, height: self.side - 2 };
+		self.split(bounds, rng, &mut rooms);
+
+		// Doors sit where a corridor squeezes through a gap in the wall around a room.
+		for y in 0..self.side {
+			for x in 0..self.side {
+				let outside = !rooms.iter().any(|room| room.contains((x, y)));
+				let touches_room = rooms.iter().any(|room| {
+					Neighborhood::VonNeumann
+						.offsets()
+						.iter()
+						.any(|(dx, dy)| room.contains((x + dx, y + dy)))
+				});
+				let wall = |dx: i64, dy: i64| self.get(x + dx, y + dy) == DungeonTile::Wall;
+				let squeezed = (wall(-1, 0) && wall(1, 0)) || (wall(0, -1) && wall(0, 1));
+				if self.get(x, y) == DungeonTile::Floor && outside && touches_room && squeezed {
+					self.set(x, y, DungeonTile::Door);
+				}
+			}
+		}
+
+		let (x, y) = rooms[0].center();
+		self.set(x, y, DungeonTile::Entrance);
+		for room in &rooms[1..] {
+			if rng.gen_bool(0.4) {
+				let x = rng.gen_range(room.x..room.x + room.width);
+				let y = rng.gen_range(room.y..room.y + room.height);
+				self.set(x, y, DungeonTile::Loot);
+			}
+		}
+	}
+
+	/// Carves a room in every leaf of the partition below `rect`, returning the center of one of
+	/// them for the parent to connect to.
+	fn split(&mut self, rect: Rect, rng: &mut StdRng, rooms: &mut Vec<Rect>) -> (i64, i64) {
+		let can_split_x = rect.width >= MIN_LEAF * 2;
+		let can_split_y = rect.height >= MIN_LEAF * 2;
+		if !can_split_x && !can_split_y {
+			let width = rng.gen_range(4.min(rect.width - 2)..=rect.width - 2);
+			let height = rng.gen_range(4.min(rect.height - 2)..=rect.height - 2);
+			let room = Rect {
+				x: rect.x + rng.gen_range(1..=rect.width - width - 1),
+				y: rect.y + rng.gen_range(1..=rect.height - height - 1),
+				width,
+				height,
+			};
+			for y in room.y..room.y + room.height {
+				for x in room.x..room.x + room.width {
+					self.set(x, y, DungeonTile::Floor);
+				}
+			}
+			rooms.push(room);
+			return room.center();
+		}
+
+		let (a, b) = if can_split_x && (rect.width >= rect.height || !can_split_y) {
+			let width = rng.gen_range(MIN_LEAF..=rect.width - MIN_LEAF);
+			(Rect { width, ..rect }, Rect { x: rect.x + width, width: rect.width - width, ..rect })
+		} else {
+			let height = rng.gen_range(MIN_LEAF..=rect.height - MIN_LEAF);
+			(
+				Rect { height, ..rect },
+				Rect { y: rect.y + height, height: rect.height - height, ..rect },
+			)
+		};
+		let from = self.split(a, rng, rooms);
+		let to = self.split(b, rng, rooms);
+		self.corridor(from, to, rng.gen_bool(0.5));
+		from
+	}
+
+	/// Carves an L-shaped corridor between two tiles, going horizontally or vertically first.
+	fn corridor(&mut self, (x0, y0): (i64, i64), (x1, y1): (i64, i64), horizontal_first: bool) {
+		let corner = if horizontal_first { (x1, y0) } else { (x0, y1) };
+		for ((ax, ay), (bx, by)) in [((x0, y0), corner), (corner, (x1, y1))] {
+			for y in ay.min(by)..=ay.max(by) {
+				for x in ax.min(bx)..=ax.max(bx) {
+					self.set(x, y, DungeonTile::Floor);
+				}
+			}
+		}
+	}
+
+	/// Grows caves from random noise, keeping only the largest cave so it is all connected.
+	fn carve_caves(&mut self, rng: &mut StdRng) {
+		for y in 1..self.side - 1 {
+			for x in 1..self.side - 1 {
+				if !rng.gen_bool(0.45) {
+					self.set(x, y, DungeonTile::Floor);
+				}
+			}
+		}
+		// A tile turns to rock when most tiles around it are rock, which smooths out caves.
+		for _ in 0..4 {
+			let mut next = Grid::new(self.side);
+			for y in 1..self.side - 1 {
+				for x in 1..self.side - 1 {
+					let walls =
+						self.walls_around(x, y) + usize::from(self.get(x, y) == DungeonTile::Wall);
+					if walls < 5 {
+						next.set(x, y, DungeonTile::Floor);
+					}
+				}
+			}
+			*self = next;
+		}
+
+		let largest = self.largest_cave();
+		for y in 0..self.side {
+			for x in 0..self.side {
+				if self.get(x, y) == DungeonTile::Floor && !largest.contains(&(x, y)) {
+					self.set(x, y, DungeonTile::Wall);
+				}
+			}
+		}
+		let Some(entrance) = largest.iter().min_by_key(|(x, y)| (*y, *x)).copied() else {
+			// Without any cave, open up a single chamber for the entrance.
+			self.set(self.side / 2, self.side / 2, DungeonTile::Entrance);
+			return;
+		};
+		self.set(entrance.0, entrance.1, DungeonTile::Entrance);
+
+		// Loot hides in the nooks of the caves.
+		let mut nooks: Vec<_> = largest
+			.into_iter()
+			.filter(|(x, y)| {
+				self.get(*x, *y) == DungeonTile::Floor && self.walls_around(*x, *y) >= 5
+			})
+			.collect();
+		nooks.sort_unstable();
+		for (x, y) in nooks.choose_multiple(rng, 4).copied().collect::<Vec<_>>() {
+			self.set(x, y, DungeonTile::Loot);
+		}
+	}
+
+	/// The floor tiles of the largest orthogonally connected cave.
+	fn largest_cave(&self) -> HashSet<(i64, i64)> {
+		let mut seen = HashSet::new();
+		let mut largest = HashSet::new();
+		for start in (0..self.side).flat_map(|y| (0..self.side).map(move |x| (x, y))) {
+			if self.get(start.0, start.1) != DungeonTile::Floor || seen.contains(&start) {
+				continue;
+			}
+			let mut cave = HashSet::from([start]);
+			let mut stack = vec![start];
+			while let Some((x, y)) = stack.pop() {
+				for (dx, dy) in Neighborhood::VonNeumann.offsets() {
+					let next = (x + dx, y + dy);
+					if self.get(next.0, next.1) == DungeonTile::Floor && cave.insert(next) {
+						stack.push(next);
+					}
+				}
+			}
+			seen.extend(cave.iter().copied());
+			if cave.len() > largest.len() {
+				largest = cave;
+			}
+		}
+		largest
+	}
+}
+
+/// The dungeons of a generated world, whose interiors are generated on first request.
+#[derive(Clone)]
+#[cfg_attr(feature = "bevy", derive(Resource))]
+pub struct Dungeons {
+	noise_gen: NoiseGenerator,
+	sites: HashSet<GridPosition>,
+	cell_size: i64,
+	interiors: HashMap<GridPosition, Layer<DungeonTile>>,
+}
+
+impl Dungeons {
+	/// Collects the `Special::Dungeon` cells of the layer, without generating any interior yet.
+	pub fn new(noise_gen: &NoiseGenerator, special: &Layer<Special>) -> Self {
+		let sites = special
+			.positions()
+			.filter(|position| special.get(*position) == Special::Dungeon)
+			.map(|position| special.get_grid_position(position))
+			.collect();
+		Self {
+			noise_gen: noise_gen.clone(),
+			sites,
+			cell_size: special.scale(),
+			interiors: HashMap::new(),
+		}
+	}
+
+	/// Gets the dungeon cell covering the given position, if there is one.
+	fn site_at(&self, position: WorldPosition) -> Option<GridPosition> {
+		let grid_position = GridPosition::new(
+			position.x.div_euclid(self.cell_size),
+			position.y.div_euclid(self.cell_size),
+			self.cell_size,
+		);
+		self.sites.contains(&grid_position).then_some(grid_position)
+	}
+
+	/// Whether a dungeon cell covers the given position.
+	pub fn contains(&self, position: WorldPosition) -> bool {
+		self.site_at(position).is_some()
+	}
+
+	/// Gets the interior of the dungeon covering the given position, generating it on first
+	/// request.
+	pub fn interior(&mut self, position: WorldPosition) -> Option<&Layer<DungeonTile>> {
+		let site = self.site_at(position)?;
+		let noise_gen = &self.noise_gen;
+		Some(self.interiors.entry(site).or_insert_with(|| {
+			let position = site.into();
+			generate_interior(noise_gen, position, DungeonStyle::of(noise_gen, position))
+		}))
+	}
+
+	/// Iterates the position of every dungeon cell, in no particular order.
+	pub fn sites(&self) -> impl Iterator<Item = WorldPosition> + '_ {
+		self.sites.iter().map(|site| (*site).into())
+	}
+
+	/// The number of interiors generated so far.
+	pub fn generated(&self) -> usize {
+		self.interiors.len()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::world::{WorldGenConfig, WorldLayers};
+
+	fn count(layer: &Layer<DungeonTile>, tile: DungeonTile) -> usize {
+		layer.positions().filter(|position| layer.get(*position) == tile).count()
+	}
+
+	#[test]
+	fn interiors_are_deterministic_per_position() {
+		let noise_gen = NoiseGenerator::new(1);
+		let a = WorldPosition::new(8, 12);
+		let b = WorldPosition::new(12, 8);
+		let interior = |position| {
+			let layer = generate_interior(&noise_gen, position, DungeonStyle::Rooms);
+			layer.positions().map(|position| layer.get(position)).collect::<Vec<_>>()
+		};
+
+		assert_eq!(interior(a), interior(a));
+		assert_ne!(interior(a), interior(b));
+	}
+
+	#[test]
+	fn interiors_are_connected_with_one_entrance() {
+		let noise_gen = NoiseGenerator::new(2);
+		for seed in 0..8 {
+			for style in [DungeonStyle::Rooms, DungeonStyle::Caves] {
+				let layer = generate_interior(&noise_gen, WorldPosition::new(seed, 0), style);
+
+				let open = layer
+					.regions_by(Neighborhood::VonNeumann, |tile| tile != DungeonTile::Wall)
+					.into_iter()
+					.filter(|region| layer.get(region[0]) != DungeonTile::Wall)
+					.count();
+				assert_eq!(open, 1, "{style:?} interior {seed} is split up");
+				assert_eq!(count(&layer, DungeonTile::Entrance), 1);
+				assert!(count(&layer, DungeonTile::Floor) > 64);
+			}
+		}
+	}
+
+	#[test]
+	fn rooms_have_doors_and_loot() {
+		let noise_gen = NoiseGenerator::new(3);
+		let layers: Vec<_> = (0..8)
+			.map(|x| generate_interior(&noise_gen, WorldPosition::new(x, 0), DungeonStyle::Rooms))
+			.collect();
+
+		assert!(layers.iter().all(|layer| count(layer, DungeonTile::Door) > 0));
+		assert!(layers.iter().any(|layer| count(layer, DungeonTile::Loot) > 0));
+	}
+
+	#[test]
+	fn interiors_are_generated_on_request() {
+		let mut world = WorldLayers::generate(&WorldGenConfig::default());
+		let site = world.dungeons.sites().next().expect("the world has dungeons");
+		assert_eq!(world.dungeons.generated(), 0);
+		assert!(world.dungeons.interior(WorldPosition::new(i64::MIN / 2, 0)).is_none());
+
+		let inside = WorldPosition::new(site.x + world.special.scale() - 1, site.y);
+		let first = world.dungeons.interior(site).unwrap().clone();
+		let again = world.dungeons.interior(inside).unwrap();
+		assert!(first.positions().all(|position| first.get(position) == again.get(position)));
+		assert_eq!(world.dungeons.generated(), 1);
+	}
+}
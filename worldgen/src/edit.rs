@@ -0,0 +1,264 @@
+#[cfg(feature = "bevy")]
+use crate::change::{CellValue, LayerResources};
+use crate::change::{ChangeLog, WorldValue};
+use crate::layer::layers::biome::Biome;
+use crate::layer::layers::detail::TerrainDetail;
+use crate::layer::layers::flora::Flora;
+use crate::layer::layers::mineral::Mineral;
+use crate::layer::layers::special::Special;
+use crate::layer::layers::terrain::TerrainFeature;
+use crate::layer::layers::territory::TerritoryId;
+use crate::layer::layers::urban::Urban;
+use crate::layer::layers::water::WaterType;
+use crate::layer::{GridPosition, Layer, Neighborhood, WorldPosition};
+#[cfg(feature = "bevy")]
+use crate::world::LayerKind;
+use std::collections::{BTreeSet, HashSet, VecDeque};
+
+/// A value a designer can pick from a palette to paint a layer with.
+pub trait Palette: WorldValue {
+	/// The values to pick from for the layer, in a stable order.
+	fn palette(layer: &Layer<Self>) -> Vec<Self>;
+}
+
+macro_rules! enum_palettes {
+	($($value:ty),*) => {
+		$(
+			impl Palette for $value {
+				fn palette(_layer: &Layer<Self>) -> Vec<Self> {
+					<$value>::ALL.to_vec()
+				}
+			}
+		)*
+	};
+}
+
+enum_palettes!(WaterType, TerrainFeature, Biome, TerrainDetail, Flora, Mineral, Urban, Special);
+
+/// Kingdoms can't be made up, so the palette holds the kingdoms of the layer besides unclaimed
+/// land.
+impl Palette for TerritoryId {
+	fn palette(layer: &Layer<Self>) -> Vec<Self> {
+		let mut kingdoms: BTreeSet<_> =
+			layer.positions().map(|position| layer.get(position)).collect();
+		kingdoms.insert(TerritoryId::UNCLAIMED);
+		kingdoms.into_iter().collect()
+	}
+}
+
+/// The outline of a [Brush].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BrushShape {
+	#[default]
+	Round,
+	Square,
+}
+
+/// Paints every cell within `radius` cells of where it is put down.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Brush {
+	pub shape: BrushShape,
+	pub radius: i64,
+}
+
+impl Brush {
+	/// The cells of the layer the brush covers when put down at the position.
+	pub fn cells<T: WorldValue>(
+		&self,
+		layer: &Layer<T>,
+		center: WorldPosition,
+	) -> Vec<GridPosition> {
+		let center = layer.get_grid_position(center);
+		let radius = self.radius.max(0);
+		(-radius..=radius)
+			.flat_map(|y| (-radius..=radius).map(move |x| (x, y)))
+			.filter(|(x, y)| self.shape == BrushShape::Square || x * x + y * y <= radius * radius)
+			.map(|(x, y)| GridPosition::new(center.x + x, center.y + y, center.scale))
+			.filter(|cell| layer.contains((*cell).into()))
+			.collect()
+	}
+}
+
+/// A change a designer makes to a layer with one of the editor's tools.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edit {
+	/// Puts the brush down at the position.
+	Paint { brush: Brush, center: WorldPosition },
+	/// Fills the area of equal cells around the position, up to its orthogonal neighbors.
+	Fill(WorldPosition),
+	/// Fills the rectangle between two opposite corners, both included.
+	Rectangle(WorldPosition, WorldPosition),
+}
+
+impl Edit {
+	/// The cells of the layer the edit changes.
+	pub fn cells<T: WorldValue>(&self, layer: &Layer<T>) -> Vec<GridPosition> {
+		match *self {
+			Edit::Paint { brush, center } => brush.cells(layer, center),
+			Edit::Fill(position) => Self::area(layer, position),
+			Edit::Rectangle(from, to) => {
+				let (from, to) = (layer.get_grid_position(from), layer.get_grid_position(to));
+				(from.y.min(to.y)..=from.y.max(to.y))
+					.flat_map(|y| (from.x.min(to.x)..=from.x.max(to.x)).map(move |x| (x, y)))
+					.map(|(x, y)| GridPosition::new(x, y, from.scale))
+					.filter(|cell| layer.contains((*cell).into()))
+					.collect()
+			}
+		}
+	}
+
+	/// Paints the cells of the edit with the value, recording every change in the log.
+	///
+	/// Returns the number of cells that changed.
+	pub fn apply<T: WorldValue>(
+		&self,
+		layer: &mut Layer<T>,
+		value: T,
+		log: &mut ChangeLog,
+	) -> usize {
+		self.cells(layer)
+			.into_iter()
+			.filter(|cell| log.set(layer, (*cell).into(), value))
+			.count()
+	}
+
+	/// The cells holding the same value as the one at the position that can be reached from it
+	/// without crossing other values.
+	fn area<T: WorldValue>(layer: &Layer<T>, position: WorldPosition) -> Vec<GridPosition> {
+		if !layer.contains(position) {
+			return Vec::new();
+		}
+
+		let start = layer.get_grid_position(position);
+		let value = layer.get_grid(start);
+		let mut seen = HashSet::from([start]);
+		let mut queue = VecDeque::from([start]);
+		let mut area = Vec::new();
+		while let Some(cell) = queue.pop_front() {
+			area.push(cell);
+			for (x, y) in Neighborhood::VonNeumann.offsets() {
+				let neighbor = GridPosition::new(cell.x + x, cell.y + y, cell.scale);
+				if layer.contains(neighbor.into())
+					&& layer.get_grid(neighbor) == value
+					&& seen.insert(neighbor)
+				{
+					queue.push_back(neighbor);
+				}
+			}
+		}
+		area
+	}
+}
+
+#[cfg(feature = "bevy")]
+impl LayerResources<'_> {
+	/// The values to pick from to paint the layer, which is empty if the layer is disabled.
+	pub fn palette(&self, kind: LayerKind) -> Vec<CellValue> {
+		fn cells<T: Palette>(layer: Option<&Layer<T>>) -> Vec<CellValue> {
+			let palette = layer.map(T::palette).unwrap_or_default();
+			palette.into_iter().map(T::into_cell).collect()
+		}
+
+		match kind {
+			LayerKind::Water => cells(self.water.as_deref()),
+			LayerKind::Terrain => cells(self.terrain.as_deref()),
+			LayerKind::Biome => cells(self.biome.as_deref()),
+			LayerKind::Detail => cells(self.detail.as_deref()),
+			LayerKind::Flora => cells(self.flora.as_deref()),
+			LayerKind::Mineral => cells(self.mineral.as_deref()),
+			LayerKind::Urban => cells(self.urban.as_deref()),
+			LayerKind::Special => cells(self.special.as_deref()),
+			LayerKind::Territory => cells(self.territory.as_deref()),
+		}
+	}
+
+	/// Paints the cells of the edit in the layer of the value, recording every change in the log.
+	///
+	/// Returns the number of cells that changed, which is none in a disabled layer.
+	pub fn edit(&mut self, edit: &Edit, value: CellValue, log: &mut ChangeLog) -> usize {
+		fn apply<T: WorldValue>(
+			layer: Option<&mut Layer<T>>,
+			edit: &Edit,
+			value: T,
+			log: &mut ChangeLog,
+		) -> usize {
+			layer.map_or(0, |layer| edit.apply(layer, value, log))
+		}
+
+		match value {
+			CellValue::Water(value) => apply(self.water.as_deref_mut(), edit, value, log),
+			CellValue::Terrain(value) => apply(self.terrain.as_deref_mut(), edit, value, log),
+			CellValue::Biome(value) => apply(self.biome.as_deref_mut(), edit, value, log),
+			CellValue::Detail(value) => apply(self.detail.as_deref_mut(), edit, value, log),
+			CellValue::Flora(value) => apply(self.flora.as_deref_mut(), edit, value, log),
+			CellValue::Mineral(value) => apply(self.mineral.as_deref_mut(), edit, value, log),
+			CellValue::Urban(value) => apply(self.urban.as_deref_mut(), edit, value, log),
+			CellValue::Special(value) => apply(self.special.as_deref_mut(), edit, value, log),
+			CellValue::Territory(value) => apply(self.territory.as_deref_mut(), edit, value, log),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn round_brushes_cut_the_corners_of_square_ones() {
+		let layer = Layer::<Flora>::new_base32(2, 6);
+		let round = Brush { shape: BrushShape::Round, radius: 2 };
+		let square = Brush { shape: BrushShape::Square, radius: 2 };
+
+		assert_eq!(round.cells(&layer, WorldPosition::new(0, 0)).len(), 13);
+		assert_eq!(square.cells(&layer, WorldPosition::new(0, 0)).len(), 25);
+		// A brush put down in a corner of the world only paints within it.
+		assert_eq!(square.cells(&layer, WorldPosition::new(-32, -32)).len(), 9);
+	}
+
+	#[test]
+	fn painting_records_only_changed_cells() {
+		let mut layer = Layer::new_base32(2, 6);
+		let mut log = ChangeLog::new();
+		let brush = Brush { shape: BrushShape::Square, radius: 1 };
+		let paint = |center| Edit::Paint { brush, center };
+
+		assert_eq!(paint(WorldPosition::new(0, 0)).apply(&mut layer, Flora::Tree, &mut log), 9);
+		assert_eq!(paint(WorldPosition::new(4, 0)).apply(&mut layer, Flora::Tree, &mut log), 3);
+		assert_eq!(log.len(), 12);
+		assert_eq!(layer.get(WorldPosition::new(-4, 4)), Flora::Tree);
+	}
+
+	#[test]
+	fn fills_stop_at_other_values() {
+		let mut layer = Layer::new_base32(0, 3);
+		let mut log = ChangeLog::new();
+		// A wall of water splits the world into a left and a right half.
+		Edit::Rectangle(WorldPosition::new(0, -4), WorldPosition::new(0, 3)).apply(
+			&mut layer,
+			WaterType::River,
+			&mut log,
+		);
+		assert_eq!(log.len(), 8);
+
+		let filled =
+			Edit::Fill(WorldPosition::new(-2, 1)).apply(&mut layer, WaterType::Lake, &mut log);
+		assert_eq!(filled, 32);
+		assert_eq!(layer.get(WorldPosition::new(-4, -4)), WaterType::Lake);
+		assert_eq!(layer.get(WorldPosition::new(0, 0)), WaterType::River);
+		assert_eq!(layer.get(WorldPosition::new(1, 0)), WaterType::None);
+	}
+
+	#[test]
+	fn territory_palettes_hold_the_kingdoms_of_the_layer() {
+		let mut layer = Layer::new_base32(2, 6);
+		layer.set(WorldPosition::new(0, 0), TerritoryId(3));
+		layer.set(WorldPosition::new(8, 0), TerritoryId(1));
+		layer.set(WorldPosition::new(12, 0), TerritoryId(3));
+
+		assert_eq!(
+			TerritoryId::palette(&layer),
+			vec![TerritoryId::UNCLAIMED, TerritoryId(1), TerritoryId(3)]
+		);
+		assert_eq!(Flora::palette(&Layer::new_base32(2, 6)), Flora::ALL.to_vec());
+	}
+}
@@ -0,0 +1,279 @@
+use crate::change::{CellChange, CellLayers, ChangeLog};
+#[cfg(feature = "bevy")]
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+/// The number of changes a [History] keeps by default, a few megabytes worth.
+pub const DEFAULT_MAX_CHANGES: usize = 1 << 16;
+
+/// The undo and redo history of the changes recorded in a [ChangeLog].
+///
+/// Every batch of changes recorded at once becomes an entry, which is undone as a whole, unless a
+/// stroke is open, in which case everything recorded until it ends goes into the same entry. The
+/// oldest entries are forgotten once the history holds more than its maximum number of changes,
+/// and a stroke outgrowing it is split into several entries so that it can be forgotten too.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "bevy", derive(Resource))]
+pub struct History {
+	undo: VecDeque<Vec<CellChange>>,
+	redo: Vec<Vec<CellChange>>,
+	/// The entry changes are recorded into, until it is committed.
+	open: Vec<CellChange>,
+	stroke: bool,
+	max_changes: usize,
+}
+
+impl Default for History {
+	fn default() -> Self {
+		Self::new(DEFAULT_MAX_CHANGES)
+	}
+}
+
+impl History {
+	/// Creates an empty history keeping at most `max_changes` changes.
+	pub fn new(max_changes: usize) -> Self {
+		Self {
+			undo: VecDeque::new(),
+			redo: Vec::new(),
+			open: Vec::new(),
+			stroke: false,
+			max_changes,
+		}
+	}
+
+	/// Records the changes of the log, taking them so the log doesn't grow forever.
+	///
+	/// New changes make whatever was undone impossible to redo.
+	pub fn record(&mut self, log: &mut ChangeLog) {
+		let changes = log.take();
+		if changes.is_empty() {
+			return;
+		}
+
+		self.redo.clear();
+		self.open.extend(changes);
+		if !self.stroke || self.len() > self.max_changes {
+			self.commit();
+		}
+	}
+
+	/// Starts a stroke, e.g. as a brush is put down, so that every change recorded until it ends
+	/// is undone at once.
+	pub fn begin_stroke(&mut self) {
+		self.stroke = true;
+	}
+
+	/// Ends the stroke, making an entry of the changes recorded during it.
+	pub fn end_stroke(&mut self) {
+		self.stroke = false;
+		self.commit();
+	}
+
+	/// Undoes the latest entry, recording the changes undoing it in the log.
+	///
+	/// Returns whether there was anything to undo.
+	pub fn undo(&mut self, layers: &mut impl CellLayers, log: &mut ChangeLog) -> bool {
+		self.record(log);
+		self.end_stroke();
+		let Some(entry) = self.undo.pop_back() else {
+			return false;
+		};
+
+		let inverse: Vec<_> = entry.iter().rev().map(CellChange::inverse).collect();
+		layers.apply_changes(&inverse, log);
+		// Undoing is not an edit of its own.
+		log.take();
+		self.redo.push(entry);
+		true
+	}
+
+	/// Redoes the latest undone entry, recording its changes in the log.
+	///
+	/// Returns whether there was anything to redo.
+	pub fn redo(&mut self, layers: &mut impl CellLayers, log: &mut ChangeLog) -> bool {
+		self.record(log);
+		self.end_stroke();
+		let Some(entry) = self.redo.pop() else {
+			return false;
+		};
+
+		layers.apply_changes(&entry, log);
+		log.take();
+		self.undo.push_back(entry);
+		true
+	}
+
+	pub fn can_undo(&self) -> bool {
+		!self.undo.is_empty() || !self.open.is_empty()
+	}
+
+	pub fn can_redo(&self) -> bool {
+		!self.redo.is_empty()
+	}
+
+	/// The number of changes kept, which never exceeds the maximum for long.
+	pub fn len(&self) -> usize {
+		self.undo.iter().chain(&self.redo).map(Vec::len).sum::<usize>() + self.open.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// The changes that can be undone, oldest first, e.g. to save along with the world's config
+	/// and replay onto the regenerated world with [CellLayers::apply_changes].
+	///
+	/// Changes forgotten to stay within the maximum are left out, so a history kept for saving
+	/// should be allowed as many changes as the edits to keep.
+	pub fn to_log(&self) -> ChangeLog {
+		self.undo.iter().flatten().chain(&self.open).copied().collect()
+	}
+
+	/// Forgets every entry, e.g. once the world is regenerated along with a new log.
+	pub fn clear(&mut self) {
+		*self = Self::new(self.max_changes);
+	}
+
+	/// Makes an entry of the open changes, forgetting the oldest entries if they no longer fit.
+	fn commit(&mut self) {
+		if !self.open.is_empty() {
+			self.undo.push_back(std::mem::take(&mut self.open));
+		}
+		while self.len() > self.max_changes && self.undo.len() > 1 {
+			self.undo.pop_front();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::change::CellValue;
+	use crate::layer::layers::flora::Flora;
+	use crate::layer::layers::urban::Urban;
+	use crate::layer::WorldPosition;
+	use crate::world::{LayerKind, WorldGenConfig, WorldLayers};
+
+	fn paint(world: &mut WorldLayers, log: &mut ChangeLog, x: i64, value: CellValue) {
+		assert!(world.set_cell(WorldPosition::new(x * 64, 0), value, log));
+	}
+
+	#[test]
+	fn undo_and_redo_restore_every_layer() {
+		let original = WorldLayers::generate(&WorldGenConfig::default());
+		let mut world = original.clone();
+		let mut log = ChangeLog::new();
+		let mut history = History::default();
+
+		paint(&mut world, &mut log, 0, CellValue::Urban(Urban::Temple));
+		paint(&mut world, &mut log, 0, CellValue::Flora(Flora::Cactus));
+		history.record(&mut log);
+		let edited = world.clone();
+
+		assert!(history.undo(&mut world, &mut log));
+		assert!(!history.undo(&mut world, &mut log));
+		for position in original.positions(LayerKind::Urban) {
+			assert_eq!(world.urban.get(position), original.urban.get(position));
+			assert_eq!(world.flora.get(position), original.flora.get(position));
+		}
+
+		assert!(history.redo(&mut world, &mut log));
+		assert!(!history.redo(&mut world, &mut log));
+		let position = WorldPosition::new(0, 0);
+		assert_eq!(world.urban.get(position), edited.urban.get(position));
+		assert_eq!(world.flora.get(position), Flora::Cactus);
+	}
+
+	#[test]
+	fn strokes_are_undone_at_once() {
+		let mut world = WorldLayers::generate(&WorldGenConfig::default());
+		let mut log = ChangeLog::new();
+		let mut history = History::default();
+
+		history.begin_stroke();
+		for x in 0..3 {
+			paint(&mut world, &mut log, x, CellValue::Flora(Flora::Mushroom));
+			history.record(&mut log);
+		}
+		history.end_stroke();
+		paint(&mut world, &mut log, 5, CellValue::Flora(Flora::Mushroom));
+		history.record(&mut log);
+
+		assert!(history.undo(&mut world, &mut log));
+		assert_ne!(world.flora.get(WorldPosition::new(5 * 64, 0)), Flora::Mushroom);
+		assert_eq!(world.flora.get(WorldPosition::new(2 * 64, 0)), Flora::Mushroom);
+		assert!(history.undo(&mut world, &mut log));
+		assert!((0..3).all(|x| world.flora.get(WorldPosition::new(x * 64, 0)) != Flora::Mushroom));
+
+		// Undoing is not an edit of its own, and new edits drop what could be redone.
+		assert!(!history.can_undo());
+		paint(&mut world, &mut log, 7, CellValue::Urban(Urban::Ruin));
+		history.record(&mut log);
+		assert!(!history.can_redo());
+	}
+
+	#[test]
+	fn the_oldest_entries_are_forgotten() {
+		let mut world = WorldLayers::generate(&WorldGenConfig::default());
+		let mut log = ChangeLog::new();
+		let mut history = History::new(4);
+
+		for x in 0..6 {
+			paint(&mut world, &mut log, x, CellValue::Urban(Urban::Ruin));
+			paint(&mut world, &mut log, x, CellValue::Flora(Flora::Palm));
+			history.record(&mut log);
+		}
+
+		assert_eq!(history.len(), 4);
+		assert!(history.undo(&mut world, &mut log));
+		assert!(history.undo(&mut world, &mut log));
+		assert!(!history.undo(&mut world, &mut log));
+		assert_eq!(world.urban.get(WorldPosition::new(3 * 64, 0)), Urban::Ruin);
+		assert!(log.is_empty());
+	}
+
+	#[test]
+	fn long_strokes_are_forgotten_too() {
+		let mut world = WorldLayers::generate(&WorldGenConfig::default());
+		let mut log = ChangeLog::new();
+		let mut history = History::new(4);
+
+		history.begin_stroke();
+		for x in 0..10 {
+			paint(&mut world, &mut log, x, CellValue::Flora(Flora::Mushroom));
+			history.record(&mut log);
+			assert!(log.is_empty());
+			assert!(history.len() <= 5);
+		}
+		history.end_stroke();
+
+		// Only the latest part of the stroke can still be undone.
+		while history.undo(&mut world, &mut log) {}
+		assert_eq!(world.flora.get(WorldPosition::new(0, 0)), Flora::Mushroom);
+		assert_ne!(world.flora.get(WorldPosition::new(9 * 64, 0)), Flora::Mushroom);
+	}
+
+	#[test]
+	fn saved_changes_replay_onto_the_regenerated_world() {
+		let config = WorldGenConfig::default();
+		let mut world = WorldLayers::generate(&config);
+		let mut log = ChangeLog::new();
+		let mut history = History::default();
+
+		paint(&mut world, &mut log, 0, CellValue::Urban(Urban::Temple));
+		history.record(&mut log);
+		history.begin_stroke();
+		paint(&mut world, &mut log, 1, CellValue::Flora(Flora::Cactus));
+		paint(&mut world, &mut log, 0, CellValue::Urban(Urban::Ruin));
+		history.record(&mut log);
+		assert!(log.is_empty());
+
+		let mut loaded = WorldLayers::generate(&config);
+		loaded.apply_changes(history.to_log().changes(), &mut ChangeLog::new());
+		for kind in [LayerKind::Urban, LayerKind::Flora] {
+			for position in world.positions(kind) {
+				assert_eq!(loaded.get_cell(kind, position), world.get_cell(kind, position));
+			}
+		}
+	}
+}
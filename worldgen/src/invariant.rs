@@ -0,0 +1,255 @@
+use crate::layer::layers::flora::Flora;
+use crate::layer::layers::mineral::Mineral;
+use crate::layer::layers::special::Special;
+use crate::layer::layers::terrain::TerrainFeature;
+use crate::layer::layers::urban::Urban;
+use crate::layer::layers::water::WaterType;
+use crate::layer::{WorldCell, WorldPosition};
+use crate::world::{LayerKind, WorldLayers};
+use std::fmt;
+
+/// Whether a cell of a world upholds an [Invariant].
+type Rule = Box<dyn Fn(&WorldLayers, WorldCell) -> bool + Send + Sync>;
+
+/// A rule over several layers that must hold for every cell of one layer of a generated world.
+pub struct Invariant {
+	/// Describes what the rule enforces, e.g. "seaweed only grows in the ocean".
+	pub name: &'static str,
+	/// The layer whose cells the rule is checked at.
+	pub layer: LayerKind,
+	rule: Rule,
+}
+
+impl Invariant {
+	pub fn new(
+		name: &'static str,
+		layer: LayerKind,
+		rule: impl Fn(&WorldLayers, WorldCell) -> bool + Send + Sync + 'static,
+	) -> Self {
+		Self { name, layer, rule: Box::new(rule) }
+	}
+
+	/// Gets every position of the layer where the rule is broken.
+	pub fn violations(&self, world: &WorldLayers) -> Vec<WorldPosition> {
+		let cell_size = world.scale(self.layer);
+		world
+			.positions(self.layer)
+			.filter(|position| !(self.rule)(world, WorldCell { position: *position, cell_size }))
+			.collect()
+	}
+}
+
+/// The registered [Invariant]s of a world.
+pub struct Invariants {
+	invariants: Vec<Invariant>,
+}
+
+impl Invariants {
+	/// Creates a registry without any invariants.
+	pub fn empty() -> Self {
+		Self { invariants: Vec::new() }
+	}
+
+	pub fn register(&mut self, invariant: Invariant) -> &mut Self {
+		self.invariants.push(invariant);
+		self
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &Invariant> {
+		self.invariants.iter()
+	}
+
+	/// Evaluates every invariant across the world.
+	pub fn check(&self, world: &WorldLayers) -> InvariantReport {
+		let violations = self
+			.invariants
+			.iter()
+			.map(|invariant| Violation {
+				invariant: invariant.name,
+				layer: invariant.layer,
+				positions: invariant.violations(world),
+			})
+			.filter(|violation| !violation.positions.is_empty())
+			.collect();
+		InvariantReport { violations }
+	}
+}
+
+/// The invariants every generated world upholds.
+impl Default for Invariants {
+	fn default() -> Self {
+		let mut invariants = Self::empty();
+		invariants
+			.register(Invariant::new(
+				"seaweed only grows in the ocean",
+				LayerKind::Flora,
+				|world, cell| {
+					world.flora.get(cell.position) != Flora::Seaweed
+						|| world.water.get(cell.position) == WaterType::Ocean
+				},
+			))
+			.register(Invariant::new(
+				"land plants never grow in water",
+				LayerKind::Flora,
+				|world, cell| {
+					matches!(world.flora.get(cell.position), Flora::None | Flora::Seaweed)
+						|| !world.water.get(cell.position).is_water()
+				},
+			))
+			.register(Invariant::new(
+				"nothing is built in water",
+				LayerKind::Urban,
+				|world, cell| {
+					world.urban.get(cell.position) == Urban::None
+						|| !world.water.get(cell.position).is_water()
+				},
+			))
+			.register(Invariant::new("mines sit on mountains", LayerKind::Urban, |world, cell| {
+				world.urban.get(cell.position) != Urban::Mine
+					|| world.terrain.get(cell.position) == TerrainFeature::Mountain
+			}))
+			.register(Invariant::new("mines sit on ore", LayerKind::Urban, |world, cell| {
+				world.urban.get(cell.position) != Urban::Mine
+					|| world.mineral.get(cell.position).is_ore()
+			}))
+			.register(Invariant::new("ports sit on the coast", LayerKind::Urban, |world, cell| {
+				world.urban.get(cell.position) != Urban::Port || world.water.is_coast(&cell)
+			}))
+			.register(Invariant::new(
+				"volcanoes are never in water",
+				LayerKind::Special,
+				|world, cell| {
+					world.special.get(cell.position) != Special::Volcano
+						|| !world.water.get(cell.position).is_water()
+				},
+			))
+			.register(Invariant::new(
+				"crystals grow from crystal deposits",
+				LayerKind::Special,
+				|world, cell| {
+					world.special.get(cell.position) != Special::Crystal
+						|| world.mineral.get(cell.position) == Mineral::Crystal
+				},
+			))
+			.register(Invariant::new(
+				"kingdoms never claim open water",
+				LayerKind::Territory,
+				|world, cell| {
+					!world.territory.get(cell.position).is_claimed()
+						|| !matches!(
+							world.water.get(cell.position),
+							WaterType::Ocean | WaterType::Lake
+						)
+				},
+			));
+		invariants
+	}
+}
+
+/// The cells breaking one [Invariant].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+	pub invariant: &'static str,
+	pub layer: LayerKind,
+	pub positions: Vec<WorldPosition>,
+}
+
+/// The outcome of checking [Invariants] against a world.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InvariantReport {
+	/// The broken invariants, each with at least one offending position.
+	pub violations: Vec<Violation>,
+}
+
+impl InvariantReport {
+	pub fn is_ok(&self) -> bool {
+		self.violations.is_empty()
+	}
+}
+
+impl fmt::Display for InvariantReport {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.is_ok() {
+			return write!(f, "all invariants hold");
+		}
+
+		for violation in &self.violations {
+			write!(
+				f,
+				"{:?} layer: {} broken at {} cells:",
+				violation.layer,
+				violation.invariant,
+				violation.positions.len()
+			)?;
+			// Listing every cell of a badly broken layer is noise, the first few locate it.
+			for position in violation.positions.iter().take(8) {
+				write!(f, " ({}, {})", position.x, position.y)?;
+			}
+			if violation.positions.len() > 8 {
+				write!(f, " ...")?;
+			}
+			writeln!(f)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::world::{WorldGenConfig, WorldPreset};
+	use rand::prelude::*;
+
+	#[test]
+	fn violations_report_offending_positions() {
+		let mut world = WorldLayers::generate(&WorldGenConfig::default());
+		let position = WorldPosition::new(0, 0);
+		world.water.set(position, WaterType::Ocean);
+		world.special.set(position, Special::Volcano);
+
+		let report = Invariants::default().check(&world);
+
+		let violation = report
+			.violations
+			.iter()
+			.find(|violation| violation.invariant == "volcanoes are never in water")
+			.expect("the volcano in the ocean is reported");
+		assert_eq!(violation.layer, LayerKind::Special);
+		assert!(violation.positions.contains(&position));
+	}
+
+	#[test]
+	fn custom_invariants_can_be_registered() {
+		let world = WorldLayers::generate(&WorldGenConfig::default());
+		let mut invariants = Invariants::empty();
+		invariants.register(Invariant::new("the world is dry", LayerKind::Water, |world, cell| {
+			!world.water.get(cell.position).is_water()
+		}));
+
+		let report = invariants.check(&world);
+
+		assert!(!report.is_ok());
+		assert_eq!(report.violations[0].invariant, "the world is dry");
+	}
+
+	#[test]
+	fn generated_worlds_uphold_the_invariants() {
+		let invariants = Invariants::default();
+		let presets = [WorldPreset::Continents, WorldPreset::Archipelago, WorldPreset::Pangaea];
+		let mut rng = StdRng::seed_from_u64(0);
+
+		for _ in 0..200 {
+			let config = WorldGenConfig {
+				seed: rng.gen(),
+				size: rng.gen_range(8..=16),
+				preset: presets[rng.gen_range(0..presets.len())],
+				smoothing: rng.gen_range(0..=2),
+				..WorldGenConfig::default()
+			};
+
+			let report = invariants.check(&WorldLayers::generate(&config));
+
+			assert!(report.is_ok(), "{config:?}\n{report}");
+		}
+	}
+}
@@ -0,0 +1,182 @@
+use crate::layer::pass::most_common;
+use crate::layer::{Layer, LayerValue, WorldPosition};
+
+impl LayerValue for bool {}
+
+/// A layer of flags, e.g. the cells of a layer matching a predicate.
+pub type Mask = Layer<bool>;
+
+impl<T: LayerValue> Layer<T> {
+	/// Converts every cell of the layer into a layer of the same scale.
+	pub fn map<U: LayerValue>(&self, f: impl Fn(T) -> U) -> Layer<U> {
+		let mut layer = Layer::new_base32(self.scale.trailing_zeros(), self.world_size);
+		// Empty cells stay empty unless the conversion fills them, so only the stored ones need
+		// converting.
+		if f(T::default()) == U::default() {
+			for (position, value) in &self.data {
+				layer.set_grid(*position, f(*value));
+			}
+		} else {
+			for position in self.positions() {
+				layer.set(position, f(self.get(position)));
+			}
+		}
+		layer
+	}
+
+	/// Combines the layer cell by cell with another one into a layer of this layer's scale.
+	///
+	/// The other layer is read at the corner of each cell, so a finer layer should be resampled
+	/// first to vote on the value of the whole cell.
+	pub fn zip<U: LayerValue, V: LayerValue>(
+		&self,
+		other: &Layer<U>,
+		f: impl Fn(T, U) -> V,
+	) -> Layer<V> {
+		let mut layer = Layer::new_base32(self.scale.trailing_zeros(), self.world_size);
+		for position in self.positions() {
+			layer.set(position, f(self.get(position), other.get(position)));
+		}
+		layer
+	}
+
+	/// The cells of the layer matching the predicate.
+	pub fn mask(&self, predicate: impl Fn(T) -> bool) -> Mask {
+		self.map(predicate)
+	}
+
+	/// Keeps the cells within the mask, emptying the others.
+	pub fn masked(&self, mask: &Mask) -> Layer<T> {
+		self.zip(mask, |value, inside| if inside { value } else { T::default() })
+	}
+
+	/// Converts the layer to cells of `1 << scale_factor` units over the same world.
+	///
+	/// Finer cells take the value of the cell they lie in, while coarser cells take the most
+	/// common value of the cells they cover, the earliest one from the bottom row up on ties.
+	pub fn resample(&self, scale_factor: u32) -> Layer<T> {
+		let mut layer = Layer::new_base32(scale_factor, self.world_size);
+		if layer.scale <= self.scale {
+			for position in layer.positions() {
+				layer.set(position, self.get(position));
+			}
+			return layer;
+		}
+
+		let (scale, cells) = (self.scale, layer.scale / self.scale);
+		for position in layer.positions() {
+			let covered = (0..cells).flat_map(|y| {
+				(0..cells).map(move |x| {
+					self.get(WorldPosition::new(position.x + x * scale, position.y + y * scale))
+				})
+			});
+			layer.set(position, most_common(covered, None).unwrap_or_default());
+		}
+		layer
+	}
+}
+
+impl Mask {
+	/// The cells within either mask.
+	pub fn union(&self, other: &Mask) -> Mask {
+		self.zip(other, |a, b| a || b)
+	}
+
+	/// The cells within both masks.
+	pub fn intersection(&self, other: &Mask) -> Mask {
+		self.zip(other, |a, b| a && b)
+	}
+
+	/// The cells within this mask but not the other.
+	pub fn difference(&self, other: &Mask) -> Mask {
+		self.zip(other, |a, b| a && !b)
+	}
+
+	/// The cells outside the mask.
+	pub fn complement(&self) -> Mask {
+		self.map(|inside| !inside)
+	}
+
+	/// The number of cells within the mask.
+	pub fn count(&self) -> usize {
+		self.data.len()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::layer::layers::flora::Flora;
+	use crate::layer::layers::water::WaterType;
+
+	#[test]
+	fn maps_convert_every_cell() {
+		let mut water = Layer::new_base32(2, 5);
+		water.set(WorldPosition::new(0, 0), WaterType::Ocean);
+		water.set(WorldPosition::new(4, 0), WaterType::River);
+
+		let wet = water.mask(|water| water != WaterType::None);
+		assert_eq!(wet.count(), 2);
+		// Conversions filling empty cells fill the whole layer.
+		let dry = water.mask(|water| water == WaterType::None);
+		assert_eq!(dry.count(), 64 - 2);
+		assert_eq!(dry.scale(), water.scale());
+		assert!(!dry.get(WorldPosition::new(5, 1)));
+	}
+
+	#[test]
+	fn masks_combine_cellwise() {
+		let mut layer = Layer::new_base32(0, 2);
+		for (x, flora) in [(-2, Flora::Tree), (-1, Flora::Tree), (0, Flora::Cactus)] {
+			layer.set(WorldPosition::new(x, 0), flora);
+		}
+		let trees = layer.mask(|flora| flora == Flora::Tree);
+		assert_eq!(trees.complement().count(), 16 - 2);
+		let plants = layer.mask(|flora| flora != Flora::None);
+		assert_eq!(trees.union(&plants).count(), 3);
+		assert_eq!(trees.intersection(&plants).count(), 2);
+		assert_eq!(plants.difference(&trees).count(), 1);
+		assert!(plants.difference(&trees).get(WorldPosition::new(0, 0)));
+		assert_eq!(layer.masked(&trees).get(WorldPosition::new(0, 0)), Flora::None);
+		assert_eq!(layer.masked(&trees).get(WorldPosition::new(-1, 0)), Flora::Tree);
+	}
+
+	#[test]
+	fn downsampling_votes_and_upsampling_spreads() {
+		let mut fine = Layer::new_base32(0, 3);
+		// Three trees and a cactus left of the origin, a cactus and a palm right of it.
+		for (x, y, flora) in [(-2, 0, Flora::Tree), (-1, 0, Flora::Tree), (-2, 1, Flora::Tree)] {
+			fine.set(WorldPosition::new(x, y), flora);
+		}
+		fine.set(WorldPosition::new(-1, 1), Flora::Cactus);
+		fine.set(WorldPosition::new(1, 0), Flora::Cactus);
+		fine.set(WorldPosition::new(0, 1), Flora::Palm);
+
+		let coarse = fine.resample(1);
+		assert_eq!(coarse.scale(), 2);
+		assert_eq!(coarse.get(WorldPosition::new(-2, 0)), Flora::Tree);
+		// The two empty cells outvote the cactus and the palm.
+		assert_eq!(coarse.get(WorldPosition::new(0, 0)), Flora::None);
+
+		let again = coarse.resample(0);
+		assert_eq!(again.scale(), 1);
+		assert!([(-2, 0), (-1, 0), (-2, 1), (-1, 1)]
+			.into_iter()
+			.all(|(x, y)| again.get(WorldPosition::new(x, y)) == Flora::Tree));
+		assert_eq!(again.mask(|flora| flora == Flora::Tree).count(), 4);
+	}
+
+	#[test]
+	fn zips_read_other_scales_at_the_corner_of_each_cell() {
+		let mut coarse = Layer::new_base32(2, 4);
+		coarse.set(WorldPosition::new(0, 0), WaterType::Lake);
+		let mut fine = Layer::new_base32(0, 4);
+		fine.set(WorldPosition::new(1, 1), Flora::Tree);
+
+		let shore =
+			fine.zip(&coarse, |flora, water| flora == Flora::Tree && water != WaterType::None);
+		assert_eq!(shore.scale(), 1);
+		assert_eq!(shore.count(), 1);
+		assert!(shore.get(WorldPosition::new(1, 1)));
+	}
+}
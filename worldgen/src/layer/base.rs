@@ -0,0 +1,153 @@
+use crate::layer::WorldPosition;
+
+/// A named stream of noise, so that each layer, and each purpose within a layer, samples noise
+/// that is independent of every other stream.
+///
+/// Streams are registered in [streams] and named `layer` or `layer.purpose`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NoiseStream {
+	pub name: &'static str,
+	pub salt: u64,
+}
+
+impl NoiseStream {
+	/// Creates a stream whose salt is the FNV-1a hash of its name.
+	pub const fn new(name: &'static str) -> Self {
+		let bytes = name.as_bytes();
+		let mut salt = 0xcbf29ce484222325u64;
+		let mut i = 0;
+		while i < bytes.len() {
+			salt ^= bytes[i] as u64;
+			salt = salt.wrapping_mul(0x100000001b3);
+			i += 1;
+		}
+		Self { name, salt }
+	}
+}
+
+/// Declares the registered [NoiseStream]s along with a list of all of them.
+macro_rules! noise_streams {
+	($($(#[$meta:meta])* $ident:ident = $name:literal;)*) => {
+		$($(#[$meta])* pub const $ident: NoiseStream = NoiseStream::new($name);)*
+
+		/// Every registered stream.
+		pub const ALL: &[NoiseStream] = &[$($ident),*];
+	};
+}
+
+/// The registry of every [NoiseStream] used to generate the world.
+pub mod streams {
+	use super::NoiseStream;
+
+	noise_streams! {
+		WATER = "water";
+		TERRAIN = "terrain";
+		BIOME = "biome";
+		DETAIL = "detail";
+		FLORA = "flora";
+		MINERAL = "mineral";
+		MINERAL_DEPOSIT = "mineral.deposit";
+		URBAN = "urban";
+		SPECIAL = "special";
+		SPECIAL_VOLCANO = "special.volcano";
+		PORTALS = "portals";
+		DUNGEON = "dungeon";
+		DUNGEON_STYLE = "dungeon.style";
+		DISASTER = "disaster";
+		DISASTER_WILDFIRE = "disaster.wildfire";
+		SEASON = "season";
+		SIMULATION = "simulation";
+		WFC = "wfc";
+		NAMES = "names";
+	}
+}
+
+#[derive(Clone)]
+pub struct NoiseGenerator {
+	seed: u32,
+}
+
+impl NoiseGenerator {
+	pub fn new(seed: u32) -> Self {
+		Self { seed }
+	}
+
+	/// Simple deterministic noise function based on coordinates and stream
+	pub fn get_noise_value(&self, pos: &WorldPosition, stream: NoiseStream) -> u32 {
+		let mut combined = mix(self.seed as u64);
+		for value in [stream.salt, pos.x as u64, pos.y as u64] {
+			combined = mix(combined ^ value);
+		}
+		// The mixed bits are already uniform, so the high half serves as the sample.
+		(combined >> 32) as u32
+	}
+}
+
+/// The SplitMix64 finalizer, spreading every input bit over the whole output.
+fn mix(mut value: u64) -> u64 {
+	value = value.wrapping_add(0x9e3779b97f4a7c15);
+	value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+	value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+	value ^ (value >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::HashSet;
+
+	#[test]
+	fn noise_is_deterministic_for_negative_positions() {
+		let noise_gen = NoiseGenerator::new(7);
+
+		for x in -32..32 {
+			let position = WorldPosition::new(x, -x);
+			assert_eq!(
+				noise_gen.get_noise_value(&position, streams::WATER),
+				noise_gen.get_noise_value(&position, streams::WATER)
+			);
+		}
+	}
+
+	#[test]
+	fn noise_is_continuous_across_the_origin() {
+		let noise_gen = NoiseGenerator::new(7);
+
+		// Every position along a row crossing the origin gets its own value, and neither side
+		// mirrors the other.
+		let values: HashSet<_> = (-64..64)
+			.map(|x| noise_gen.get_noise_value(&WorldPosition::new(x, 0), streams::WATER))
+			.collect();
+		assert_eq!(values.len(), 128);
+
+		for x in 1..64 {
+			assert_ne!(
+				noise_gen.get_noise_value(&WorldPosition::new(x, 0), streams::WATER),
+				noise_gen.get_noise_value(&WorldPosition::new(-x, 0), streams::WATER)
+			);
+		}
+	}
+
+	#[test]
+	fn registered_streams_have_unique_salts() {
+		let names: HashSet<_> = streams::ALL.iter().map(|stream| stream.name).collect();
+		let salts: HashSet<_> = streams::ALL.iter().map(|stream| stream.salt).collect();
+
+		assert_eq!(names.len(), streams::ALL.len(), "two streams share a name");
+		assert_eq!(salts.len(), streams::ALL.len(), "two streams share a salt");
+	}
+
+	#[test]
+	fn streams_are_independent() {
+		let noise_gen = NoiseGenerator::new(7);
+
+		for x in -16..16 {
+			let position = WorldPosition::new(x, x);
+			let values: HashSet<_> = streams::ALL
+				.iter()
+				.map(|stream| noise_gen.get_noise_value(&position, *stream))
+				.collect();
+			assert_eq!(values.len(), streams::ALL.len());
+		}
+	}
+}
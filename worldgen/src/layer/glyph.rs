@@ -0,0 +1,143 @@
+use crate::layer::render::LayerRender;
+use crate::layer::WorldCell;
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// An error raised while loading a [GlyphManifest].
+#[derive(Debug, thiserror::Error)]
+pub enum GlyphManifestError {
+	#[error("failed to read glyph manifest: {0}")]
+	Io(#[from] std::io::Error),
+	#[error("failed to parse glyph manifest: {0}")]
+	Parse(#[from] serde_json::Error),
+}
+
+/// Describes a texture atlas and which glyph in it each layer value uses.
+///
+/// Glyphs are keyed by [GlyphValue::glyph_name], e.g. `"flora.tree"`.
+#[derive(Clone, Default, Deserialize)]
+pub struct GlyphManifest {
+	/// The atlas image, relative to the assets directory.
+	pub image: String,
+	/// The size of a single glyph in pixels.
+	pub tile_size: (u32, u32),
+	/// The number of glyph columns in the atlas.
+	pub columns: u32,
+	/// The number of glyph rows in the atlas.
+	pub rows: u32,
+	/// The atlas index of each named glyph.
+	pub glyphs: HashMap<String, usize>,
+}
+
+impl GlyphManifest {
+	/// Loads a manifest from a JSON file.
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, GlyphManifestError> {
+		let contents = std::fs::read_to_string(path)?;
+		Ok(serde_json::from_str(&contents)?)
+	}
+
+	/// The number of glyphs in the atlas.
+	pub fn len(&self) -> usize {
+		(self.columns * self.rows) as usize
+	}
+
+	/// Whether the atlas holds no glyphs.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+/// A loaded texture atlas used to draw [GlyphValue]s.
+#[derive(Resource, Clone, Default)]
+pub struct GlyphAtlas {
+	image: Handle<Image>,
+	layout: Handle<TextureAtlasLayout>,
+	glyphs: HashMap<String, usize>,
+}
+
+impl GlyphAtlas {
+	/// Loads the atlas image and layout described by the [GlyphManifest].
+	///
+	/// Glyphs that point outside the atlas are dropped so that they fall back to colored squares.
+	pub fn new(
+		manifest: &GlyphManifest,
+		asset_server: &AssetServer,
+		layouts: &mut Assets<TextureAtlasLayout>,
+	) -> Self {
+		if manifest.is_empty() {
+			return Self::default();
+		}
+
+		let layout = TextureAtlasLayout::from_grid(
+			UVec2::new(manifest.tile_size.0, manifest.tile_size.1),
+			manifest.columns,
+			manifest.rows,
+			None,
+			None,
+		);
+		let glyphs = manifest
+			.glyphs
+			.iter()
+			.filter(|(_, index)| **index < manifest.len())
+			.map(|(name, index)| (name.clone(), *index))
+			.collect();
+
+		Self {
+			image: asset_server.load(manifest.image.as_str()),
+			layout: layouts.add(layout),
+			glyphs,
+		}
+	}
+
+	/// Gets the atlas index of the named glyph, if the atlas has one.
+	pub fn index(&self, name: &str) -> Option<usize> {
+		self.glyphs.get(name).copied()
+	}
+}
+
+/// A value that can be drawn as a glyph from a [GlyphAtlas].
+pub trait GlyphValue: LayerRender {
+	/// The name of the glyph in the [GlyphManifest], or `None` if the value is not drawn.
+	fn glyph_name(&self) -> Option<&'static str>;
+
+	/// Renders the value as a glyph tinted with [LayerRender::get_color].
+	///
+	/// Falls back to [LayerRender::render] when the atlas has no glyph for the value.
+	fn render_glyph(
+		&self,
+		commands: &mut Commands,
+		world_cell: &WorldCell,
+		atlas: &GlyphAtlas,
+	) -> Option<Entity> {
+		let name = self.glyph_name()?;
+		if atlas.index(name).is_none() {
+			return self.render(commands, world_cell);
+		}
+		self.render_glyph_colored(commands, world_cell, atlas, self.get_color())
+	}
+
+	/// Renders the value as a glyph tinted with the given color, falling back to a square of the
+	/// color when the atlas has no glyph for the value.
+	fn render_glyph_colored(
+		&self,
+		commands: &mut Commands,
+		world_cell: &WorldCell,
+		atlas: &GlyphAtlas,
+		color: Color,
+	) -> Option<Entity> {
+		let name = self.glyph_name()?;
+		let sprite = match atlas.index(name) {
+			Some(index) => Sprite {
+				image: atlas.image.clone(),
+				texture_atlas: Some(TextureAtlas { layout: atlas.layout.clone(), index }),
+				color,
+				custom_size: Some(world_cell.size()),
+				..default()
+			},
+			None => Sprite { color, custom_size: Some(world_cell.size()), ..default() },
+		};
+		Some(commands.spawn((sprite, world_cell.transform())).id())
+	}
+}
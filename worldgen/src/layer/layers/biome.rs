@@ -1,10 +1,14 @@
-use crate::layer::base::NoiseGenerator;
+use crate::layer::base::{streams, NoiseGenerator};
 use crate::layer::layers::terrain::TerrainFeature;
 use crate::layer::layers::water::WaterType;
-use crate::layer::{Layer, LayerFactory, LayerValue, WorldCell, WorldPosition};
+#[cfg(feature = "bevy")]
+use crate::layer::render::LayerRender;
+use crate::layer::{Layer, LayerFactory, LayerValue, WorldCell};
+#[cfg(feature = "bevy")]
 use bevy::prelude::*;
 
-#[derive(Clone, Copy, Default, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Biome {
 	#[default]
 	Desert,
@@ -15,21 +19,20 @@ pub enum Biome {
 	Snow,
 }
 
-impl LayerValue for Biome {
-	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) {
+impl LayerValue for Biome {}
+
+#[cfg(feature = "bevy")]
+impl LayerRender for Biome {
+	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) -> Option<Entity> {
 		let color = self.get_color();
 
-		commands.spawn((
-			Sprite {
-				color,
-				custom_size: Some(Vec2::new(
-					world_cell.cell_size as f32,
-					world_cell.cell_size as f32,
-				)),
-				..default()
-			},
-			Transform::from_xyz(world_cell.position.x as f32, world_cell.position.y as f32, 0.0),
-		));
+		let entity = commands
+			.spawn((
+				Sprite { color, custom_size: Some(world_cell.size()), ..default() },
+				world_cell.transform(),
+			))
+			.id();
+		Some(entity)
 	}
 
 	fn get_color(&self) -> Color {
@@ -45,6 +48,10 @@ impl LayerValue for Biome {
 }
 
 impl Biome {
+	/// Every biome.
+	pub const ALL: [Biome; 6] =
+		[Biome::Desert, Biome::Grassland, Biome::Forest, Biome::Jungle, Biome::Tundra, Biome::Snow];
+
 	pub fn from_values(
 		biome_value: u32,
 		water_type: WaterType,
@@ -100,12 +107,13 @@ impl BiomeLayerFactory {
 impl LayerFactory<Biome, (Layer<WaterType>, Layer<TerrainFeature>)> for BiomeLayerFactory {
 	fn create_value(
 		&self,
-		pos: WorldPosition,
+		cell: WorldCell,
 		deps: &(Layer<WaterType>, Layer<TerrainFeature>),
 	) -> Biome {
+		let pos = cell.position;
 		let water_type = deps.0.get(pos);
 		let terrain_feature = deps.1.get(pos);
-		let value = self.noise_gen.get_noise_value(&pos, 0);
+		let value = self.noise_gen.get_noise_value(&pos, streams::BIOME);
 		Biome::from_values(value, water_type, terrain_feature)
 	}
 }
@@ -113,14 +121,10 @@ impl LayerFactory<Biome, (Layer<WaterType>, Layer<TerrainFeature>)> for BiomeLay
 pub fn generate_biome_layer(
 	noise_gen: &NoiseGenerator,
 	scale: u32,
+	world_size: u32,
 	water_layer: Layer<WaterType>,
 	terrain_layer: Layer<TerrainFeature>,
 ) -> Layer<Biome> {
 	let factory = BiomeLayerFactory::new(noise_gen.clone());
-	crate::layer::generate_layer(
-		scale,
-		(water_layer, terrain_layer),
-		factory,
-		crate::layer::AllGridPositions::new(scale),
-	)
+	crate::layer::generate_layer(scale, world_size, (water_layer, terrain_layer), factory)
 }
@@ -1,11 +1,15 @@
-use crate::layer::base::NoiseGenerator;
+use crate::layer::base::{streams, NoiseGenerator};
 use crate::layer::layers::biome::Biome;
 use crate::layer::layers::terrain::TerrainFeature;
 use crate::layer::layers::water::WaterType;
-use crate::layer::{Layer, LayerFactory, LayerValue, WorldCell, WorldPosition};
+#[cfg(feature = "bevy")]
+use crate::layer::render::LayerRender;
+use crate::layer::{Layer, LayerFactory, LayerValue, WorldCell};
+#[cfg(feature = "bevy")]
 use bevy::prelude::*;
 
-#[derive(Clone, Copy, Default, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TerrainDetail {
 	#[default]
 	None,
@@ -16,25 +20,24 @@ pub enum TerrainDetail {
 	Ice,
 }
 
-impl LayerValue for TerrainDetail {
-	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) {
+impl LayerValue for TerrainDetail {}
+
+#[cfg(feature = "bevy")]
+impl LayerRender for TerrainDetail {
+	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) -> Option<Entity> {
 		let color = self.get_color();
 
 		if *self == TerrainDetail::None {
-			return;
+			return None;
 		}
 
-		commands.spawn((
-			Sprite {
-				color,
-				custom_size: Some(Vec2::new(
-					world_cell.cell_size as f32,
-					world_cell.cell_size as f32,
-				)),
-				..default()
-			},
-			Transform::from_xyz(world_cell.position.x as f32, world_cell.position.x as f32, 0.0),
-		));
+		let entity = commands
+			.spawn((
+				Sprite { color, custom_size: Some(world_cell.size()), ..default() },
+				world_cell.transform(),
+			))
+			.id();
+		Some(entity)
 	}
 
 	fn get_color(&self) -> Color {
@@ -50,11 +53,22 @@ impl LayerValue for TerrainDetail {
 }
 
 impl TerrainDetail {
+	/// Every terrain detail.
+	pub const ALL: [TerrainDetail; 6] = [
+		TerrainDetail::None,
+		TerrainDetail::Rock,
+		TerrainDetail::Sand,
+		TerrainDetail::Mud,
+		TerrainDetail::Snow,
+		TerrainDetail::Ice,
+	];
+
 	pub fn from_values(
 		detail_value: u32,
 		water_type: WaterType,
-		terrain_feature: TerrainFeature,
+		_terrain_feature: TerrainFeature,
 		biome: Biome,
+		on_coast: bool,
 	) -> Self {
 		// normalize detail_value to 0-1
 		let detail_value = detail_value as f64 / u32::MAX as f64;
@@ -65,6 +79,9 @@ impl TerrainDetail {
 			} else {
 				Self::None
 			}
+		} else if on_coast && !matches!(biome, Biome::Tundra | Biome::Snow) {
+			// Beaches border the ocean
+			Self::Sand
 		} else {
 			match biome {
 				Biome::Desert => {
@@ -112,20 +129,23 @@ impl LayerFactory<TerrainDetail, (Layer<WaterType>, Layer<TerrainFeature>, Layer
 {
 	fn create_value(
 		&self,
-		pos: WorldPosition,
+		cell: WorldCell,
 		deps: &(Layer<WaterType>, Layer<TerrainFeature>, Layer<Biome>),
 	) -> TerrainDetail {
+		let pos = cell.position;
 		let water_type = deps.0.get(pos);
 		let terrain_feature = deps.1.get(pos);
 		let biome = deps.2.get(pos);
-		let value = self.noise_gen.get_noise_value(&pos, 0);
-		TerrainDetail::from_values(value, water_type, terrain_feature, biome)
+		let value = self.noise_gen.get_noise_value(&pos, streams::DETAIL);
+		let on_coast = deps.0.is_coast(&cell);
+		TerrainDetail::from_values(value, water_type, terrain_feature, biome, on_coast)
 	}
 }
 
 pub fn generate_detail_layer(
 	noise_gen: &NoiseGenerator,
 	scale: u32,
+	world_size: u32,
 	water_layer: Layer<WaterType>,
 	terrain_layer: Layer<TerrainFeature>,
 	biome_layer: Layer<Biome>,
@@ -133,8 +153,8 @@ pub fn generate_detail_layer(
 	let factory = DetailLayerFactory::new(noise_gen.clone());
 	crate::layer::generate_layer(
 		scale,
+		world_size,
 		(water_layer, terrain_layer, biome_layer),
 		factory,
-		crate::layer::AllGridPositions::new(scale),
 	)
 }
@@ -1,12 +1,18 @@
-use crate::layer::base::NoiseGenerator;
+use crate::layer::base::{streams, NoiseGenerator};
+#[cfg(feature = "bevy")]
+use crate::layer::glyph::GlyphValue;
 use crate::layer::layers::biome::Biome;
 use crate::layer::layers::detail::TerrainDetail;
 use crate::layer::layers::terrain::TerrainFeature;
 use crate::layer::layers::water::WaterType;
-use crate::layer::{Layer, LayerFactory, LayerValue, WorldCell, WorldPosition};
+#[cfg(feature = "bevy")]
+use crate::layer::render::LayerRender;
+use crate::layer::{Layer, LayerFactory, LayerValue, WorldCell};
+#[cfg(feature = "bevy")]
 use bevy::prelude::*;
 
-#[derive(Clone, Copy, Default, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Flora {
 	#[default]
 	None,
@@ -19,10 +25,13 @@ pub enum Flora {
 	Seaweed,
 }
 
-impl LayerValue for Flora {
-	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) {
+impl LayerValue for Flora {}
+
+#[cfg(feature = "bevy")]
+impl LayerRender for Flora {
+	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) -> Option<Entity> {
 		let color = match self {
-			Flora::None => return,
+			Flora::None => return None,
 			Flora::Tree => Color::srgb(0.2, 0.5, 0.1),
 			Flora::Palm => Color::srgb(0.3, 0.6, 0.2),
 			Flora::Cactus => Color::srgb(0.2, 0.4, 0.1),
@@ -32,30 +41,21 @@ impl LayerValue for Flora {
 			Flora::Seaweed => Color::srgb(0.1, 0.4, 0.2),
 		};
 
-		commands.spawn((
-			Sprite {
-				color,
-				custom_size: Some(Vec2::new(
-					world_cell.cell_size as f32,
-					world_cell.cell_size as f32,
-				)),
-				..default()
-			},
-			Transform::from_xyz(
-				world_cell.position.x as f32 * world_cell.cell_size as f32,
-				world_cell.position.y as f32 * world_cell.cell_size as f32,
-				0.0,
-			),
-		));
+		let entity = commands
+			.spawn((
+				Sprite { color, custom_size: Some(world_cell.size()), ..default() },
+				world_cell.transform(),
+			))
+			.id();
+		Some(entity)
 	}
 
 	fn get_color(&self) -> Color {
 		match self {
 			Self::None => Color::NONE,
 			Self::Tree => Color::srgb(0.2, 0.5, 0.2),
-			Self::Palm => Color::srgb(0.3, 0.6, 0.3),
+			Self::Palm | Self::Bush => Color::srgb(0.3, 0.6, 0.3),
 			Self::Cactus => Color::srgb(0.3, 0.7, 0.3),
-			Self::Bush => Color::srgb(0.3, 0.6, 0.3),
 			Self::Flower => Color::srgb(0.8, 0.4, 0.8),
 			Self::Mushroom => Color::srgb(0.7, 0.7, 0.7),
 			Self::Seaweed => Color::srgb(0.2, 0.4, 0.2),
@@ -63,13 +63,41 @@ impl LayerValue for Flora {
 	}
 }
 
+#[cfg(feature = "bevy")]
+impl GlyphValue for Flora {
+	fn glyph_name(&self) -> Option<&'static str> {
+		match self {
+			Flora::None => None,
+			Flora::Tree => Some("flora.tree"),
+			Flora::Palm => Some("flora.palm"),
+			Flora::Cactus => Some("flora.cactus"),
+			Flora::Bush => Some("flora.bush"),
+			Flora::Flower => Some("flora.flower"),
+			Flora::Mushroom => Some("flora.mushroom"),
+			Flora::Seaweed => Some("flora.seaweed"),
+		}
+	}
+}
+
 impl Flora {
+	/// Every flora.
+	pub const ALL: [Flora; 8] = [
+		Flora::None,
+		Flora::Tree,
+		Flora::Palm,
+		Flora::Cactus,
+		Flora::Bush,
+		Flora::Flower,
+		Flora::Mushroom,
+		Flora::Seaweed,
+	];
+
 	pub fn from_values(
 		flora_value: f64,
 		water_type: WaterType,
-		terrain_feature: TerrainFeature,
+		_terrain_feature: TerrainFeature,
 		biome: Biome,
-		detail: TerrainDetail,
+		_detail: TerrainDetail,
 	) -> Self {
 		if water_type.is_water() {
 			if water_type == WaterType::Ocean {
@@ -92,17 +120,6 @@ impl Flora {
 						Self::None
 					}
 				}
-				Biome::Forest => {
-					if flora_value > 0.8 {
-						Self::Tree
-					} else if flora_value > 0.6 {
-						Self::Bush
-					} else if flora_value > 0.4 {
-						Self::Flower
-					} else {
-						Self::None
-					}
-				}
 				Biome::Jungle => {
 					if flora_value > 0.7 {
 						Self::Tree
@@ -157,14 +174,15 @@ impl
 {
 	fn create_value(
 		&self,
-		pos: WorldPosition,
+		cell: WorldCell,
 		deps: &(Layer<WaterType>, Layer<TerrainFeature>, Layer<Biome>, Layer<TerrainDetail>),
 	) -> Flora {
+		let pos = cell.position;
 		let water_type = deps.0.get(pos);
 		let terrain_feature = deps.1.get(pos);
 		let biome = deps.2.get(pos);
 		let detail = deps.3.get(pos);
-		let value = self.noise_gen.get_noise_value(&pos, 4);
+		let value = self.noise_gen.get_noise_value(&pos, streams::FLORA);
 		Flora::from_values(
 			value as f64 / u32::MAX as f64,
 			water_type,
@@ -178,6 +196,7 @@ impl
 pub fn generate_flora_layer(
 	noise_gen: &NoiseGenerator,
 	scale: u32,
+	world_size: u32,
 	water_layer: Layer<WaterType>,
 	terrain_layer: Layer<TerrainFeature>,
 	biome_layer: Layer<Biome>,
@@ -186,8 +205,8 @@ pub fn generate_flora_layer(
 	let factory = FloraLayerFactory::new(noise_gen.clone());
 	crate::layer::generate_layer(
 		scale,
+		world_size,
 		(water_layer, terrain_layer, biome_layer, detail_layer),
 		factory,
-		crate::layer::AllGridPositions::new(scale),
 	)
 }
@@ -0,0 +1,275 @@
+use crate::layer::base::{streams, NoiseGenerator};
+#[cfg(feature = "bevy")]
+use crate::layer::glyph::GlyphValue;
+use crate::layer::layers::special::Special;
+use crate::layer::layers::terrain::TerrainFeature;
+use crate::layer::layers::water::WaterType;
+#[cfg(feature = "bevy")]
+use crate::layer::render::LayerRender;
+use crate::layer::{Layer, LayerFactory, LayerValue, WorldCell, WorldPosition};
+#[cfg(feature = "bevy")]
+use bevy::prelude::*;
+
+/// The side of a deposit block, in cells of the mineral layer.
+const DEPOSIT_CELLS: i64 = 4;
+
+/// The mineral resources lying under a cell.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mineral {
+	#[default]
+	None,
+	Iron,
+	Gold,
+	Coal,
+	Crystal,
+	Oil,
+}
+
+impl LayerValue for Mineral {}
+
+#[cfg(feature = "bevy")]
+impl LayerRender for Mineral {
+	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) -> Option<Entity> {
+		if *self == Mineral::None {
+			return None;
+		}
+
+		let entity = commands
+			.spawn((
+				Sprite {
+					color: self.get_color(),
+					custom_size: Some(world_cell.size()),
+					..default()
+				},
+				world_cell.transform(),
+			))
+			.id();
+		Some(entity)
+	}
+
+	fn get_color(&self) -> Color {
+		match self {
+			Mineral::None => Color::NONE,
+			Mineral::Iron => Color::srgb(0.6, 0.3, 0.2),
+			Mineral::Gold => Color::srgb(0.9, 0.8, 0.1),
+			Mineral::Coal => Color::srgb(0.1, 0.1, 0.1),
+			Mineral::Crystal => Color::srgb(0.7, 0.6, 1.0),
+			Mineral::Oil => Color::srgb(0.2, 0.1, 0.2),
+		}
+	}
+}
+
+#[cfg(feature = "bevy")]
+impl GlyphValue for Mineral {
+	fn glyph_name(&self) -> Option<&'static str> {
+		match self {
+			Mineral::None => None,
+			Mineral::Iron => Some("mineral.iron"),
+			Mineral::Gold => Some("mineral.gold"),
+			Mineral::Coal => Some("mineral.coal"),
+			Mineral::Crystal => Some("mineral.crystal"),
+			Mineral::Oil => Some("mineral.oil"),
+		}
+	}
+}
+
+impl Mineral {
+	/// Every mineral.
+	pub const ALL: [Mineral; 6] = [
+		Mineral::None,
+		Mineral::Iron,
+		Mineral::Gold,
+		Mineral::Coal,
+		Mineral::Crystal,
+		Mineral::Oil,
+	];
+
+	/// Whether mines dig for the mineral, which holds for everything but oil.
+	pub fn is_ore(&self) -> bool {
+		!matches!(self, Mineral::None | Mineral::Oil)
+	}
+
+	/// Creates the mineral of a cell from the geology around it, where `deposit_value` is shared
+	/// by the cells of one deposit and `richness` varies from cell to cell within it.
+	///
+	/// Cells near a volcano hold crystal and gold regardless of the terrain.
+	pub fn from_values(
+		deposit_value: f64,
+		richness: f64,
+		water_type: WaterType,
+		terrain_feature: TerrainFeature,
+		volcanic: bool,
+	) -> Self {
+		if water_type.is_water() || deposit_value < 0.6 || richness < 0.3 {
+			return Self::None;
+		}
+
+		// Rank the deposit among the deposits, from the most to the least common mineral.
+		let grade = (deposit_value - 0.6) / 0.4;
+		if volcanic {
+			return if grade > 0.4 { Self::Crystal } else { Self::Gold };
+		}
+		match terrain_feature {
+			TerrainFeature::Mountain => {
+				if grade > 0.85 {
+					Self::Gold
+				} else if grade > 0.35 {
+					Self::Iron
+				} else {
+					Self::Coal
+				}
+			}
+			TerrainFeature::Canyon | TerrainFeature::Cliff => {
+				if grade > 0.9 {
+					Self::Crystal
+				} else if grade > 0.5 {
+					Self::Iron
+				} else {
+					Self::Coal
+				}
+			}
+			TerrainFeature::Valley => {
+				if grade > 0.5 {
+					Self::Oil
+				} else {
+					Self::Coal
+				}
+			}
+			TerrainFeature::Plains => {
+				if grade > 0.75 {
+					Self::Oil
+				} else {
+					Self::None
+				}
+			}
+		}
+	}
+}
+
+pub struct MineralLayerFactory {
+	noise_gen: NoiseGenerator,
+	/// The volcanoes heating the rock around them.
+	volcanoes: Vec<WorldPosition>,
+}
+
+impl MineralLayerFactory {
+	pub fn new(noise_gen: NoiseGenerator, volcanoes: Vec<WorldPosition>) -> Self {
+		Self { noise_gen, volcanoes }
+	}
+
+	/// Whether a volcano erupts within the cell or the cells around it.
+	fn is_volcanic(&self, cell: &WorldCell) -> bool {
+		let near = |volcano: i64, corner: i64| {
+			(corner - cell.cell_size..corner + cell.cell_size * 2).contains(&volcano)
+		};
+		self.volcanoes
+			.iter()
+			.any(|volcano| near(volcano.x, cell.position.x) && near(volcano.y, cell.position.y))
+	}
+}
+
+impl LayerFactory<Mineral, (Layer<WaterType>, Layer<TerrainFeature>)> for MineralLayerFactory {
+	fn create_value(
+		&self,
+		cell: WorldCell,
+		deps: &(Layer<WaterType>, Layer<TerrainFeature>),
+	) -> Mineral {
+		let pos = cell.position;
+		let water_type = deps.0.get(pos);
+		if water_type.is_water() {
+			return Mineral::None;
+		}
+
+		// Cells share the deposit of the block they lie in, looked up from a position jittered
+		// by up to a cell so deposits don't come out as squares.
+		let richness = self.noise_gen.get_noise_value(&pos, streams::MINERAL);
+		let jitter = |bits: u32| ((bits & 3) as i64 - 1) * cell.cell_size;
+		let block = cell.cell_size * DEPOSIT_CELLS;
+		let deposit = WorldPosition::new(
+			(pos.x + jitter(richness)).div_euclid(block) * block,
+			(pos.y + jitter(richness >> 2)).div_euclid(block) * block,
+		);
+		let deposit_value = self.noise_gen.get_noise_value(&deposit, streams::MINERAL_DEPOSIT);
+
+		Mineral::from_values(
+			deposit_value as f64 / u32::MAX as f64,
+			(richness >> 4) as f64 / (u32::MAX >> 4) as f64,
+			water_type,
+			deps.1.get(pos),
+			self.is_volcanic(&cell),
+		)
+	}
+}
+
+/// Generates the mineral layer, where the volcanoes placed by
+/// [crate::layer::layers::special::place_volcanoes] turn the rock around them into crystal
+/// and gold.
+pub fn generate_mineral_layer(
+	noise_gen: &NoiseGenerator,
+	scale: u32,
+	world_size: u32,
+	water_layer: Layer<WaterType>,
+	terrain_layer: Layer<TerrainFeature>,
+	volcanoes: Layer<Special>,
+) -> Layer<Mineral> {
+	let volcanoes = volcanoes
+		.positions()
+		.filter(|position| volcanoes.get(*position) == Special::Volcano)
+		.collect();
+	let factory = MineralLayerFactory::new(noise_gen.clone(), volcanoes);
+	crate::layer::generate_layer(scale, world_size, (water_layer, terrain_layer), factory)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::layer::Neighborhood;
+
+	#[test]
+	fn minerals_follow_the_geology() {
+		let mineral = |terrain_feature, volcanic| {
+			Mineral::from_values(0.99, 1.0, WaterType::None, terrain_feature, volcanic)
+		};
+
+		assert_eq!(mineral(TerrainFeature::Mountain, false), Mineral::Gold);
+		assert_eq!(mineral(TerrainFeature::Canyon, false), Mineral::Crystal);
+		assert_eq!(mineral(TerrainFeature::Valley, false), Mineral::Oil);
+		assert_eq!(mineral(TerrainFeature::Plains, true), Mineral::Crystal);
+		assert_eq!(
+			Mineral::from_values(0.99, 1.0, WaterType::River, TerrainFeature::Mountain, true),
+			Mineral::None
+		);
+		assert_eq!(
+			Mineral::from_values(0.5, 1.0, WaterType::None, TerrainFeature::Mountain, false),
+			Mineral::None
+		);
+	}
+
+	#[test]
+	fn deposits_cluster() {
+		let water = Layer::new_base32(2, 8);
+		let mut terrain = Layer::new_base32(4, 8);
+		for position in terrain.positions() {
+			terrain.set(position, TerrainFeature::Mountain);
+		}
+		let volcanoes = Layer::new_base32(0, 8);
+		let layer =
+			generate_mineral_layer(&NoiseGenerator::new(7), 0, 8, water, terrain, volcanoes);
+
+		// Scattered at this density, only about a third of the ore would touch ore of its kind.
+		let ore: Vec<_> =
+			layer.positions().filter(|position| layer.get(*position).is_ore()).collect();
+		let clustered = ore
+			.iter()
+			.filter(|position| {
+				let cell = WorldCell { position: **position, cell_size: 1 };
+				layer
+					.neighbors(&cell, Neighborhood::VonNeumann)
+					.any(|neighbor| neighbor == layer.get(**position))
+			})
+			.count();
+		assert!(!ore.is_empty());
+		assert!(clustered * 3 > ore.len() * 2, "{clustered} of {} ore cells clustered", ore.len());
+	}
+}
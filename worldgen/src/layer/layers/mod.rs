@@ -1,7 +1,9 @@
 pub mod biome;
 pub mod detail;
 pub mod flora;
+pub mod mineral;
 pub mod special;
 pub mod terrain;
+pub mod territory;
 pub mod urban;
 pub mod water;
@@ -0,0 +1,191 @@
+use crate::layer::base::{streams, NoiseGenerator};
+#[cfg(feature = "bevy")]
+use crate::layer::glyph::GlyphValue;
+use crate::layer::layers::mineral::Mineral;
+use crate::layer::layers::terrain::TerrainFeature;
+use crate::layer::layers::urban::Urban;
+use crate::layer::layers::water::WaterType;
+use crate::layer::placement::{SitePlacer, SiteRule};
+#[cfg(feature = "bevy")]
+use crate::layer::render::LayerRender;
+use crate::layer::{Layer, LayerValue, WorldCell};
+#[cfg(feature = "bevy")]
+use bevy::prelude::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Special {
+	#[default]
+	None,
+	Volcano,
+	Geyser,
+	Crystal,
+	Portal,
+	Ruins,
+	Temple,
+	Dungeon,
+}
+
+impl LayerValue for Special {}
+
+impl Special {
+	/// Every special site.
+	pub const ALL: [Special; 8] = [
+		Special::None,
+		Special::Volcano,
+		Special::Geyser,
+		Special::Crystal,
+		Special::Portal,
+		Special::Ruins,
+		Special::Temple,
+		Special::Dungeon,
+	];
+}
+
+#[cfg(feature = "bevy")]
+impl LayerRender for Special {
+	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) -> Option<Entity> {
+		let color = match self {
+			Special::None => return None,
+			Special::Volcano => Color::srgb(0.8, 0.2, 0.0),
+			Special::Geyser => Color::srgb(0.0, 0.8, 0.8),
+			Special::Crystal => Color::srgb(0.8, 0.8, 1.0),
+			Special::Portal => Color::srgb(0.8, 0.0, 0.8),
+			Special::Ruins => Color::srgb(0.6, 0.6, 0.6),
+			Special::Temple => Color::srgb(0.8, 0.8, 0.6),
+			Special::Dungeon => Color::srgb(0.4, 0.4, 0.4),
+		};
+
+		let entity = commands
+			.spawn((
+				Sprite { color, custom_size: Some(world_cell.size()), ..default() },
+				world_cell.transform(),
+			))
+			.id();
+		Some(entity)
+	}
+
+	fn get_color(&self) -> Color {
+		match self {
+			Special::None => Color::NONE,
+			Special::Volcano => Color::srgb(0.8, 0.2, 0.0),
+			Special::Geyser => Color::srgb(0.0, 0.8, 0.8),
+			Special::Crystal => Color::srgb(0.8, 0.8, 1.0),
+			Special::Portal => Color::srgb(0.8, 0.0, 0.8),
+			Special::Ruins => Color::srgb(0.6, 0.6, 0.6),
+			Special::Temple => Color::srgb(0.8, 0.8, 0.6),
+			Special::Dungeon => Color::srgb(0.4, 0.4, 0.4),
+		}
+	}
+}
+
+#[cfg(feature = "bevy")]
+impl GlyphValue for Special {
+	fn glyph_name(&self) -> Option<&'static str> {
+		match self {
+			Special::None => None,
+			Special::Volcano => Some("special.volcano"),
+			Special::Geyser => Some("special.geyser"),
+			Special::Crystal => Some("special.crystal"),
+			Special::Portal => Some("special.portal"),
+			Special::Ruins => Some("special.ruins"),
+			Special::Temple => Some("special.temple"),
+			Special::Dungeon => Some("special.dungeon"),
+		}
+	}
+}
+
+/// Places the volcanoes of the world on land mountains, far apart and at most two per
+/// landmass.
+///
+/// Volcanoes are placed ahead of the other sites, since the mineral layer forms around them.
+pub fn place_volcanoes(
+	noise_gen: &NoiseGenerator,
+	scale: u32,
+	world_size: u32,
+	water_layer: &Layer<WaterType>,
+	terrain_layer: &Layer<TerrainFeature>,
+) -> Layer<Special> {
+	let mut layer = Layer::new_base32(scale, world_size);
+	let landmasses = water_layer.landmasses();
+	SitePlacer::new(noise_gen.clone(), streams::SPECIAL_VOLCANO)
+		.with_regions(|position| landmasses.region_at(position).map(|region| region.id))
+		.rule(SiteRule::new(Special::Volcano, 48.0).max_per_region(2).only_where(|cell| {
+			!water_layer.get(cell.position).is_water()
+				&& terrain_layer.get(cell.position) == TerrainFeature::Mountain
+		}))
+		.place(&mut layer);
+	layer
+}
+
+/// Scatters the remaining special sites around the volcanoes placed by [place_volcanoes], each
+/// kind spaced out and capped per landmass or ocean.
+pub fn generate_special_layer(
+	noise_gen: &NoiseGenerator,
+	volcanoes: Layer<Special>,
+	water_layer: &Layer<WaterType>,
+	terrain_layer: &Layer<TerrainFeature>,
+	mineral_layer: &Layer<Mineral>,
+	urban_layer: &Layer<Urban>,
+) -> Layer<Special> {
+	let mut layer = volcanoes;
+	let landmasses = water_layer.landmasses();
+	let on_land = |cell: WorldCell| !water_layer.get(cell.position).is_water();
+	let unbuilt = |cell: WorldCell| on_land(cell) && urban_layer.get(cell.position) == Urban::None;
+
+	SitePlacer::new(noise_gen.clone(), streams::SPECIAL)
+		.with_regions(|position| landmasses.region_at(position).map(|region| region.id))
+		.rule(
+			SiteRule::new(Special::Geyser, 32.0)
+				.max_per_region(2)
+				.only_where(|cell| water_layer.get(cell.position).is_water()),
+		)
+		.rule(
+			SiteRule::new(Special::Crystal, 12.0)
+				.max_per_region(6)
+				.only_where(|cell| mineral_layer.get(cell.position) == Mineral::Crystal),
+		)
+		.rule(SiteRule::new(Special::Portal, 96.0).max_per_region(1).only_where(unbuilt))
+		.rule(SiteRule::new(Special::Temple, 64.0).max_per_region(1).only_where(unbuilt))
+		.rule(SiteRule::new(Special::Ruins, 32.0).max_per_region(3).only_where(unbuilt))
+		.rule(SiteRule::new(Special::Dungeon, 24.0).max_per_region(4).only_where(|cell| {
+			on_land(cell)
+				&& matches!(
+					terrain_layer.get(cell.position),
+					TerrainFeature::Mountain | TerrainFeature::Canyon | TerrainFeature::Cliff
+				)
+		}))
+		.place(&mut layer);
+	layer
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::world::{WorldGenConfig, WorldLayers};
+
+	#[test]
+	fn special_sites_are_rare_and_spread_out() {
+		let world = WorldLayers::generate(&WorldGenConfig { seed: 2, ..WorldGenConfig::default() });
+		let sites: Vec<_> = world
+			.special
+			.positions()
+			.filter(|position| world.special.get(*position) != Special::None)
+			.collect();
+		let cells = world.special.positions().count();
+
+		assert!(!sites.is_empty());
+		assert!(sites.len() * 1000 < cells, "{} sites in {cells} cells", sites.len());
+		let cell_size = world.special.scale();
+		let volcanoes: Vec<_> = sites
+			.iter()
+			.filter(|position| world.special.get(**position) == Special::Volcano)
+			.collect();
+		for (index, a) in volcanoes.iter().enumerate() {
+			for b in &volcanoes[index + 1..] {
+				let (x, y) = ((a.x - b.x) / cell_size, (a.y - b.y) / cell_size);
+				assert!(x * x + y * y >= 48 * 48, "volcanoes at {a:?} and {b:?}");
+			}
+		}
+	}
+}
@@ -1,9 +1,13 @@
-use crate::layer::base::NoiseGenerator;
+use crate::layer::base::{streams, NoiseGenerator};
 use crate::layer::layers::water::WaterType;
-use crate::layer::{Layer, LayerFactory, LayerValue, WorldCell, WorldPosition};
+#[cfg(feature = "bevy")]
+use crate::layer::render::LayerRender;
+use crate::layer::{Layer, LayerFactory, LayerValue, WorldCell};
+#[cfg(feature = "bevy")]
 use bevy::prelude::*;
 
-#[derive(Clone, Copy, Default, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TerrainFeature {
 	#[default]
 	Plains,
@@ -13,25 +17,20 @@ pub enum TerrainFeature {
 	Cliff,
 }
 
-impl LayerValue for TerrainFeature {
-	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) {
+impl LayerValue for TerrainFeature {}
+
+#[cfg(feature = "bevy")]
+impl LayerRender for TerrainFeature {
+	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) -> Option<Entity> {
 		let color = self.get_color();
 
-		commands.spawn((
-			Sprite {
-				color,
-				custom_size: Some(Vec2::new(
-					world_cell.cell_size as f32,
-					world_cell.cell_size as f32,
-				)),
-				..default()
-			},
-			Transform::from_xyz(
-				world_cell.position.x as f32 * world_cell.cell_size as f32,
-				world_cell.position.y as f32 * world_cell.cell_size as f32,
-				0.0,
-			),
-		));
+		let entity = commands
+			.spawn((
+				Sprite { color, custom_size: Some(world_cell.size()), ..default() },
+				world_cell.transform(),
+			))
+			.id();
+		Some(entity)
 	}
 
 	fn get_color(&self) -> Color {
@@ -46,6 +45,15 @@ impl LayerValue for TerrainFeature {
 }
 
 impl TerrainFeature {
+	/// Every terrain feature.
+	pub const ALL: [TerrainFeature; 5] = [
+		TerrainFeature::Plains,
+		TerrainFeature::Mountain,
+		TerrainFeature::Valley,
+		TerrainFeature::Canyon,
+		TerrainFeature::Cliff,
+	];
+
 	pub fn from_values(terrain_value: f64, water_type: WaterType) -> Self {
 		if water_type.is_water() {
 			return Self::Plains;
@@ -76,9 +84,10 @@ impl TerrainLayerFactory {
 }
 
 impl LayerFactory<TerrainFeature, Layer<WaterType>> for TerrainLayerFactory {
-	fn create_value(&self, pos: WorldPosition, water_layer: &Layer<WaterType>) -> TerrainFeature {
+	fn create_value(&self, cell: WorldCell, water_layer: &Layer<WaterType>) -> TerrainFeature {
+		let pos = cell.position;
 		let water_type = water_layer.get(pos);
-		let value = self.noise_gen.get_noise_value(&pos, 1);
+		let value = self.noise_gen.get_noise_value(&pos, streams::TERRAIN);
 		TerrainFeature::from_values(value as f64 / u32::MAX as f64, water_type)
 	}
 }
@@ -86,13 +95,9 @@ impl LayerFactory<TerrainFeature, Layer<WaterType>> for TerrainLayerFactory {
 pub fn generate_terrain_layer(
 	noise_gen: &NoiseGenerator,
 	scale: u32,
+	world_size: u32,
 	water_layer: Layer<WaterType>,
 ) -> Layer<TerrainFeature> {
 	let factory = TerrainLayerFactory::new(noise_gen.clone());
-	crate::layer::generate_layer(
-		scale,
-		water_layer,
-		factory,
-		crate::layer::AllGridPositions::new(scale),
-	)
+	crate::layer::generate_layer(scale, world_size, water_layer, factory)
 }
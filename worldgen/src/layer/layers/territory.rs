@@ -0,0 +1,230 @@
+use crate::layer::layers::terrain::TerrainFeature;
+use crate::layer::layers::urban::Urban;
+use crate::layer::layers::water::WaterType;
+#[cfg(feature = "bevy")]
+use crate::layer::render::LayerRender;
+#[cfg(feature = "bevy")]
+use crate::layer::WorldCell;
+use crate::layer::{Layer, LayerValue, Neighborhood, WorldPosition};
+#[cfg(feature = "bevy")]
+use bevy::prelude::*;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// The kingdom a cell belongs to, numbered from 1 in the row order of their capitals.
+///
+/// The default `TerritoryId(0)` marks unclaimed cells.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TerritoryId(pub u32);
+
+impl TerritoryId {
+	pub const UNCLAIMED: TerritoryId = TerritoryId(0);
+
+	pub fn is_claimed(&self) -> bool {
+		*self != Self::UNCLAIMED
+	}
+}
+
+impl LayerValue for TerritoryId {}
+
+#[cfg(feature = "bevy")]
+impl LayerRender for TerritoryId {
+	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) -> Option<Entity> {
+		if !self.is_claimed() {
+			return None;
+		}
+
+		let entity = commands
+			.spawn((
+				Sprite {
+					color: self.get_color(),
+					custom_size: Some(world_cell.size()),
+					..default()
+				},
+				world_cell.transform(),
+			))
+			.id();
+		Some(entity)
+	}
+
+	fn get_color(&self) -> Color {
+		if !self.is_claimed() {
+			return Color::NONE;
+		}
+
+		// Step around the hue wheel by the golden angle so neighboring IDs look distinct.
+		Color::hsla((self.0 as f32 * 137.508) % 360.0, 0.6, 0.5, 0.6)
+	}
+}
+
+/// The cost of claiming a cell, or `None` if kingdoms can't spread into it.
+///
+/// Mountains and rivers cost the most to cross, so borders tend to follow them.
+pub fn claim_cost(water_type: WaterType, terrain_feature: TerrainFeature) -> Option<u32> {
+	let water_cost = match water_type {
+		WaterType::None => 0,
+		WaterType::Swamp => 2,
+		WaterType::River => 6,
+		WaterType::Ocean | WaterType::Lake => return None,
+	};
+	let terrain_cost = match terrain_feature {
+		TerrainFeature::Plains | TerrainFeature::Valley => 0,
+		TerrainFeature::Canyon | TerrainFeature::Cliff => 3,
+		TerrainFeature::Mountain => 8,
+	};
+	Some(1 + water_cost + terrain_cost)
+}
+
+/// Grows a kingdom from every `Urban::City` over the land, each cell going to the capital it is
+/// cheapest to reach according to [claim_cost].
+///
+/// Cells costing more than `max_cost` to reach from any capital stay unclaimed.
+pub fn generate_territory_layer(
+	scale: u32,
+	world_size: u32,
+	water_layer: Layer<WaterType>,
+	terrain_layer: Layer<TerrainFeature>,
+	urban_layer: Layer<Urban>,
+	max_cost: u32,
+) -> Layer<TerritoryId> {
+	let mut layer = Layer::new_base32(scale, world_size);
+	let cell_size = layer.scale();
+	let cost = |position: WorldPosition| {
+		claim_cost(water_layer.get(position), terrain_layer.get(position))
+	};
+
+	// Dijkstra from all capitals at once, where ties go to the lower ID so the result doesn't
+	// depend on the order cells are visited in.
+	let mut best: HashMap<WorldPosition, (u32, TerritoryId)> = HashMap::new();
+	let mut queue = BinaryHeap::new();
+	let capitals = layer.positions().filter(|position| urban_layer.get(*position) == Urban::City);
+	for (index, capital) in capitals.enumerate() {
+		let territory = TerritoryId(index as u32 + 1);
+		best.insert(capital, (0, territory));
+		queue.push(Reverse((0, territory, capital.x, capital.y)));
+	}
+
+	while let Some(Reverse((distance, territory, x, y))) = queue.pop() {
+		let position = WorldPosition::new(x, y);
+		if best.get(&position) != Some(&(distance, territory)) {
+			continue;
+		}
+		layer.set(position, territory);
+
+		for (dx, dy) in Neighborhood::VonNeumann.offsets() {
+			let neighbor = WorldPosition::new(x + dx * cell_size, y + dy * cell_size);
+			if !layer.contains(neighbor) {
+				continue;
+			}
+			let Some(step) = cost(neighbor) else {
+				continue;
+			};
+			let candidate = (distance.saturating_add(step), territory);
+			if candidate.0 <= max_cost
+				&& best.get(&neighbor).map_or(true, |known| candidate < *known)
+			{
+				best.insert(neighbor, candidate);
+				queue.push(Reverse((candidate.0, territory, neighbor.x, neighbor.y)));
+			}
+		}
+	}
+
+	layer
+}
+
+#[cfg(feature = "bevy")]
+impl Layer<TerritoryId> {
+	/// Draws a line along every edge where a territory meets another territory or unclaimed
+	/// land, returning the spawned sprites.
+	pub fn render_borders(&self, commands: &mut Commands, color: Color) -> Vec<Entity> {
+		let width = (self.scale as f32 / 8.0).max(1.0);
+		let mut entities = Vec::new();
+		for (position, territory) in &self.data {
+			let cell = WorldCell { position: (*position).into(), cell_size: self.scale };
+			let center = cell.transform().translation;
+			let half = self.scale as f32 / 2.0;
+
+			for (x, y) in Neighborhood::VonNeumann.offsets() {
+				let neighbor = WorldPosition::new(
+					cell.position.x + x * self.scale,
+					cell.position.y + y * self.scale,
+				);
+				// Edges between two territories are drawn once, from the lower ID.
+				let other = self.get(neighbor);
+				if other == *territory || (other.is_claimed() && other < *territory) {
+					continue;
+				}
+
+				let size = if x == 0 {
+					Vec2::new(self.scale as f32, width)
+				} else {
+					Vec2::new(width, self.scale as f32)
+				};
+				let offset = Vec3::new(x as f32 * half, y as f32 * half, 1.0);
+				entities.push(
+					commands
+						.spawn((
+							Sprite { color, custom_size: Some(size), ..default() },
+							Transform::from_translation(center + offset),
+						))
+						.id(),
+				);
+			}
+		}
+		entities
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// An 8x8 world of land with a capital on the west and east edges, and a mountain ridge
+	/// running north to south at the given column.
+	fn strip(ridge: i64) -> (Layer<WaterType>, Layer<TerrainFeature>, Layer<Urban>) {
+		let water = Layer::new_base32(0, 3);
+		let mut terrain = Layer::new_base32(0, 3);
+		let mut urban = Layer::new_base32(0, 3);
+		for y in -4..4 {
+			terrain.set(WorldPosition::new(ridge, y), TerrainFeature::Mountain);
+		}
+		urban.set(WorldPosition::new(-4, 0), Urban::City);
+		urban.set(WorldPosition::new(3, 0), Urban::City);
+		(water, terrain, urban)
+	}
+
+	#[test]
+	fn every_capital_rules_its_own_cell() {
+		let (water, terrain, urban) = strip(0);
+		let layer = generate_territory_layer(0, 3, water, terrain, urban, u32::MAX);
+
+		assert_eq!(layer.get(WorldPosition::new(-4, 0)), TerritoryId(1));
+		assert_eq!(layer.get(WorldPosition::new(3, 0)), TerritoryId(2));
+	}
+
+	#[test]
+	fn borders_follow_mountains() {
+		// Without the ridge, the eastern capital would be closer to the cells at x = 0.
+		let (water, terrain, urban) = strip(1);
+		let layer = generate_territory_layer(0, 3, water, terrain, urban, u32::MAX);
+
+		for y in -4..4 {
+			assert_eq!(layer.get(WorldPosition::new(0, y)), TerritoryId(1));
+			assert_eq!(layer.get(WorldPosition::new(2, y)), TerritoryId(2));
+		}
+	}
+
+	#[test]
+	fn water_and_distant_cells_stay_unclaimed() {
+		let (mut water, terrain, urban) = strip(0);
+		for y in -4..4 {
+			water.set(WorldPosition::new(-1, y), WaterType::Ocean);
+		}
+		let layer = generate_territory_layer(0, 3, water, terrain, urban, 2);
+
+		assert_eq!(layer.get(WorldPosition::new(-1, 0)), TerritoryId::UNCLAIMED);
+		assert_eq!(layer.get(WorldPosition::new(-2, 0)), TerritoryId(1));
+		assert_eq!(layer.get(WorldPosition::new(-2, 3)), TerritoryId::UNCLAIMED);
+	}
+}
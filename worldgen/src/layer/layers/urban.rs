@@ -1,13 +1,20 @@
-use crate::layer::base::NoiseGenerator;
+use crate::layer::base::{streams, NoiseGenerator};
+#[cfg(feature = "bevy")]
+use crate::layer::glyph::GlyphValue;
 use crate::layer::layers::biome::Biome;
 use crate::layer::layers::detail::TerrainDetail;
 use crate::layer::layers::flora::Flora;
+use crate::layer::layers::mineral::Mineral;
 use crate::layer::layers::terrain::TerrainFeature;
 use crate::layer::layers::water::WaterType;
-use crate::layer::{Layer, LayerFactory, LayerValue, WorldCell, WorldPosition};
+#[cfg(feature = "bevy")]
+use crate::layer::render::LayerRender;
+use crate::layer::{Layer, LayerFactory, LayerValue, Neighborhood, WorldCell};
+#[cfg(feature = "bevy")]
 use bevy::prelude::*;
 
-#[derive(Clone, Copy, Default, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Urban {
 	#[default]
 	None,
@@ -20,10 +27,13 @@ pub enum Urban {
 	Ruin,
 }
 
-impl LayerValue for Urban {
-	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) {
+impl LayerValue for Urban {}
+
+#[cfg(feature = "bevy")]
+impl LayerRender for Urban {
+	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) -> Option<Entity> {
 		let color = match self {
-			Urban::None => return,
+			Urban::None => return None,
 			Urban::House => Color::srgb(0.7, 0.7, 0.7),
 			Urban::Farm => Color::srgb(0.8, 0.8, 0.6),
 			Urban::City => Color::srgb(0.5, 0.5, 0.5),
@@ -33,21 +43,13 @@ impl LayerValue for Urban {
 			Urban::Ruin => Color::srgb(0.6, 0.6, 0.6),
 		};
 
-		commands.spawn((
-			Sprite {
-				color,
-				custom_size: Some(Vec2::new(
-					world_cell.cell_size as f32,
-					world_cell.cell_size as f32,
-				)),
-				..default()
-			},
-			Transform::from_xyz(
-				world_cell.position.x as f32 * world_cell.cell_size as f32,
-				world_cell.position.y as f32 * world_cell.cell_size as f32,
-				0.0,
-			),
-		));
+		let entity = commands
+			.spawn((
+				Sprite { color, custom_size: Some(world_cell.size()), ..default() },
+				world_cell.transform(),
+			))
+			.id();
+		Some(entity)
 	}
 
 	fn get_color(&self) -> Color {
@@ -64,30 +66,61 @@ impl LayerValue for Urban {
 	}
 }
 
+#[cfg(feature = "bevy")]
+impl GlyphValue for Urban {
+	fn glyph_name(&self) -> Option<&'static str> {
+		match self {
+			Urban::None => None,
+			Urban::House => Some("urban.house"),
+			Urban::Farm => Some("urban.farm"),
+			Urban::City => Some("urban.city"),
+			Urban::Port => Some("urban.port"),
+			Urban::Mine => Some("urban.mine"),
+			Urban::Temple => Some("urban.temple"),
+			Urban::Ruin => Some("urban.ruin"),
+		}
+	}
+}
+
 impl Urban {
+	/// Every urban value.
+	pub const ALL: [Urban; 8] = [
+		Urban::None,
+		Urban::House,
+		Urban::Farm,
+		Urban::City,
+		Urban::Port,
+		Urban::Mine,
+		Urban::Temple,
+		Urban::Ruin,
+	];
+
+	/// Creates the urban value of a cell, where `on_harbor` marks coastal land that is adjacent
+	/// to both the ocean and other land.
+	///
+	/// Mines are only dug into mountains holding ore.
+	#[allow(clippy::too_many_arguments)]
 	pub fn from_values(
 		urban_value: f64,
 		water_type: WaterType,
 		terrain_feature: TerrainFeature,
-		biome: Biome,
-		detail: TerrainDetail,
-		flora: Flora,
+		_biome: Biome,
+		_detail: TerrainDetail,
+		_flora: Flora,
+		mineral: Mineral,
+		on_harbor: bool,
 	) -> Self {
 		if water_type.is_water() {
-			if urban_value > 0.8 {
-				Self::Port
-			} else {
-				Self::None
-			}
+			Self::None
+		} else if on_harbor && urban_value > 0.8 {
+			Self::Port
+		} else if terrain_feature == TerrainFeature::Mountain
+			&& mineral.is_ore()
+			&& urban_value > 0.6
+		{
+			Self::Mine
 		} else {
 			match terrain_feature {
-				TerrainFeature::Mountain => {
-					if urban_value > 0.7 {
-						Self::Mine
-					} else {
-						Self::None
-					}
-				}
 				TerrainFeature::Plains => {
 					if urban_value > 0.9 {
 						Self::City
@@ -118,26 +151,38 @@ impl UrbanLayerFactory {
 impl
 	LayerFactory<
 		Urban,
-		(Layer<WaterType>, Layer<TerrainFeature>, Layer<Biome>, Layer<TerrainDetail>, Layer<Flora>),
+		(
+			Layer<WaterType>,
+			Layer<TerrainFeature>,
+			Layer<Biome>,
+			Layer<TerrainDetail>,
+			Layer<Flora>,
+			Layer<Mineral>,
+		),
 	> for UrbanLayerFactory
 {
 	fn create_value(
 		&self,
-		pos: WorldPosition,
+		cell: WorldCell,
 		deps: &(
 			Layer<WaterType>,
 			Layer<TerrainFeature>,
 			Layer<Biome>,
 			Layer<TerrainDetail>,
 			Layer<Flora>,
+			Layer<Mineral>,
 		),
 	) -> Urban {
+		let pos = cell.position;
 		let water_type = deps.0.get(pos);
 		let terrain_feature = deps.1.get(pos);
 		let biome = deps.2.get(pos);
 		let detail = deps.3.get(pos);
 		let flora = deps.4.get(pos);
-		let value = self.noise_gen.get_noise_value(&pos, 5);
+		let mineral = deps.5.get(pos);
+		let value = self.noise_gen.get_noise_value(&pos, streams::URBAN);
+		let on_harbor = deps.0.is_coast(&cell)
+			&& deps.0.neighbors(&cell, Neighborhood::Moore).any(|water| !water.is_water());
 		Urban::from_values(
 			value as f64 / u32::MAX as f64,
 			water_type,
@@ -145,24 +190,29 @@ impl
 			biome,
 			detail,
 			flora,
+			mineral,
+			on_harbor,
 		)
 	}
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn generate_urban_layer(
 	noise_gen: &NoiseGenerator,
 	scale: u32,
+	world_size: u32,
 	water_layer: Layer<WaterType>,
 	terrain_layer: Layer<TerrainFeature>,
 	biome_layer: Layer<Biome>,
 	detail_layer: Layer<TerrainDetail>,
 	flora_layer: Layer<Flora>,
+	mineral_layer: Layer<Mineral>,
 ) -> Layer<Urban> {
 	let factory = UrbanLayerFactory::new(noise_gen.clone());
 	crate::layer::generate_layer(
 		scale,
-		(water_layer, terrain_layer, biome_layer, detail_layer, flora_layer),
+		world_size,
+		(water_layer, terrain_layer, biome_layer, detail_layer, flora_layer, mineral_layer),
 		factory,
-		crate::layer::AllGridPositions::new(scale),
 	)
 }
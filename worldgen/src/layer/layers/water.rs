@@ -0,0 +1,129 @@
+use crate::layer::base::{streams, NoiseGenerator};
+use crate::layer::region::RegionMap;
+#[cfg(feature = "bevy")]
+use crate::layer::render::LayerRender;
+use crate::layer::{Layer, LayerFactory, LayerValue, Neighborhood, WorldCell};
+#[cfg(feature = "bevy")]
+use bevy::prelude::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WaterType {
+	#[default]
+	None,
+	Ocean,
+	Lake,
+	River,
+	Swamp,
+}
+
+impl LayerValue for WaterType {}
+
+#[cfg(feature = "bevy")]
+impl LayerRender for WaterType {
+	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) -> Option<Entity> {
+		let color = self.get_color();
+
+		if *self == WaterType::None {
+			return None;
+		}
+
+		let entity = commands
+			.spawn((
+				Sprite { color, custom_size: Some(world_cell.size()), ..default() },
+				world_cell.transform(),
+			))
+			.id();
+		Some(entity)
+	}
+
+	fn get_color(&self) -> Color {
+		match self {
+			WaterType::None => Color::NONE,
+			WaterType::Ocean => Color::srgb(0.0, 0.2, 0.8),
+			WaterType::Lake => Color::srgb(0.0, 0.4, 0.8),
+			WaterType::River => Color::srgb(0.0, 0.6, 0.8),
+			WaterType::Swamp => Color::srgb(0.0, 0.5, 0.3),
+		}
+	}
+}
+
+impl WaterType {
+	/// Every water type.
+	pub const ALL: [WaterType; 5] =
+		[WaterType::None, WaterType::Ocean, WaterType::Lake, WaterType::River, WaterType::Swamp];
+
+	pub fn from_value(water_value: f64) -> Self {
+		if water_value > 0.8 {
+			Self::Ocean
+		} else if water_value > 0.6 {
+			Self::Lake
+		} else if water_value > 0.4 {
+			Self::River
+		} else if water_value > 0.2 {
+			Self::Swamp
+		} else {
+			Self::None
+		}
+	}
+
+	pub fn is_water(&self) -> bool {
+		*self != Self::None
+	}
+}
+
+/// Whether a region of the water layer is land, including its lakes, rivers and swamps, or open
+/// ocean.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Landmass {
+	Land,
+	Ocean,
+}
+
+impl Layer<WaterType> {
+	/// Labels the continents and islands of the world, along with the oceans between them.
+	pub fn landmasses(&self) -> RegionMap<Landmass> {
+		RegionMap::new(self, Neighborhood::VonNeumann, |water| match water {
+			WaterType::Ocean => Landmass::Ocean,
+			_ => Landmass::Land,
+		})
+	}
+
+	/// Whether the cell is land bordering the ocean.
+	pub fn is_coast(&self, cell: &WorldCell) -> bool {
+		!self.get(cell.position).is_water()
+			&& self
+				.neighbors(cell, Neighborhood::VonNeumann)
+				.any(|water| water == WaterType::Ocean)
+	}
+}
+
+pub struct WaterLayerFactory {
+	noise_gen: NoiseGenerator,
+	water_level: f64,
+}
+
+impl WaterLayerFactory {
+	/// Creates a factory whose noise is scaled by `water_level`, where `1.0` leaves it unchanged.
+	pub fn new(noise_gen: NoiseGenerator, water_level: f64) -> Self {
+		Self { noise_gen, water_level }
+	}
+}
+
+impl LayerFactory<WaterType, ()> for WaterLayerFactory {
+	fn create_value(&self, cell: WorldCell, _deps: &()) -> WaterType {
+		let pos = cell.position;
+		let value = self.noise_gen.get_noise_value(&pos, streams::WATER);
+		WaterType::from_value(value as f64 / u32::MAX as f64 * self.water_level)
+	}
+}
+
+pub fn generate_water_layer(
+	noise_gen: &NoiseGenerator,
+	scale: u32,
+	world_size: u32,
+	water_level: f64,
+) -> Layer<WaterType> {
+	let factory = WaterLayerFactory::new(noise_gen.clone(), water_level);
+	crate::layer::generate_layer(scale, world_size, (), factory)
+}
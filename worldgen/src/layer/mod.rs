@@ -0,0 +1,524 @@
+pub mod algebra;
+pub mod base;
+#[cfg(feature = "bevy")]
+pub mod glyph;
+pub mod layers;
+pub mod pass;
+pub mod placement;
+pub mod region;
+#[cfg(feature = "bevy")]
+pub mod render;
+pub mod wfc;
+#[cfg(feature = "bevy")]
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// A position relative to the entire world.
+///
+/// The world is centered on the origin, so coordinates may be negative.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorldPosition {
+	pub x: i64,
+	pub y: i64,
+}
+
+impl WorldPosition {
+	pub fn new(x: i64, y: i64) -> Self {
+		Self { x, y }
+	}
+}
+
+/// A cell that gets filled in relative to the world.
+#[derive(Clone, Copy)]
+pub struct WorldCell {
+	pub position: WorldPosition,
+	pub cell_size: i64,
+}
+
+/// A value that can be stored in a layer.
+///
+/// Rendering lives in the separate `LayerRender` trait behind the `bevy` feature.
+pub trait LayerValue: Clone + Copy + Default + PartialEq + Send + Sync + 'static {}
+
+/// A factory that creates values for a layer.
+pub trait LayerFactory<T: LayerValue, D> {
+	/// Creates the value of the given cell, which may look at the neighborhood of the cell in
+	/// the dependency layers through [Layer::neighbors].
+	fn create_value(&self, cell: WorldCell, deps: &D) -> T;
+}
+
+/// A position relative to the grid, i.e., subdivisions of the world.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GridPosition {
+	pub x: i64,
+	pub y: i64,
+	pub scale: i64,
+}
+
+impl GridPosition {
+	pub fn new(x: i64, y: i64, scale: i64) -> Self {
+		Self { x, y, scale }
+	}
+}
+
+/// You can always iterate over a grid by going up to the scale.
+impl Iterator for GridPosition {
+	type Item = GridPosition;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.x += 1;
+		if self.x >= self.scale {
+			self.x = 0;
+			self.y += 1;
+		}
+
+		if self.y >= self.scale {
+			return None;
+		}
+
+		Some(*self)
+	}
+}
+
+impl GridPosition {
+	/// The chunk of `size` by `size` cells containing the cell.
+	pub fn chunk(&self, size: i64) -> ChunkPosition {
+		ChunkPosition { x: self.x.div_euclid(size), y: self.y.div_euclid(size), size }
+	}
+}
+
+/// A square block of cells of a layer, `size` cells wide, so that whatever mirrors a layer a
+/// block at a time, like a texture, can tell which blocks to rebuild.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkPosition {
+	pub x: i64,
+	pub y: i64,
+	pub size: i64,
+}
+
+impl From<GridPosition> for WorldPosition {
+	fn from(grid_position: GridPosition) -> Self {
+		WorldPosition {
+			x: grid_position.x * grid_position.scale,
+			y: grid_position.y * grid_position.scale,
+		}
+	}
+}
+
+/// The shape of the neighborhood around a cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Neighborhood {
+	/// The four orthogonally adjacent cells.
+	VonNeumann,
+	/// The eight orthogonally and diagonally adjacent cells.
+	Moore,
+	/// Every cell whose center is within the given number of cells.
+	Radius(i64),
+}
+
+impl Neighborhood {
+	/// The offsets of the neighboring cells, in cells, excluding the center.
+	pub fn offsets(&self) -> Vec<(i64, i64)> {
+		match self {
+			Neighborhood::VonNeumann => vec![(0, 1), (1, 0), (0, -1), (-1, 0)],
+			Neighborhood::Moore => {
+				vec![(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)]
+			}
+			Neighborhood::Radius(radius) => {
+				let radius = *radius;
+				(-radius..=radius)
+					.flat_map(|y| (-radius..=radius).map(move |x| (x, y)))
+					.filter(|(x, y)| (*x, *y) != (0, 0) && x * x + y * y <= radius * radius)
+					.collect()
+			}
+		}
+	}
+}
+
+/// A layer contains a grid of values.
+#[derive(Clone)]
+#[cfg_attr(feature = "bevy", derive(Resource))]
+pub struct Layer<T: LayerValue> {
+	data: HashMap<GridPosition, T>,
+	scale: i64,
+	world_size: u32,
+	/// The cells changed since the changes were last taken, or `None` while untracked.
+	dirty: Option<HashSet<GridPosition>>,
+}
+
+impl<T: LayerValue> Layer<T> {
+	/// Creates an empty layer with cells of `1 << scale_factor` units covering a world of
+	/// `1 << world_size` units per side.
+	///
+	/// Panics if the world is larger than [MAX_WORLD_SIZE], as its cells could not be counted.
+	pub fn new_base32(scale_factor: u32, world_size: u32) -> Self {
+		assert!(world_size <= MAX_WORLD_SIZE, "world size {world_size} exceeds {MAX_WORLD_SIZE}");
+		let scale = 1 << scale_factor;
+		Self { data: HashMap::new(), scale, world_size, dirty: None }
+	}
+
+	/// Gets the grid position for the given [WorldPosition].
+	///
+	/// Rounds towards negative infinity, so cells left of and below the origin are as wide as
+	/// any other.
+	pub fn get_grid_position(&self, position: WorldPosition) -> GridPosition {
+		GridPosition {
+			x: position.x.div_euclid(self.scale),
+			y: position.y.div_euclid(self.scale),
+			scale: self.scale,
+		}
+	}
+
+	/// Get the value at the given [GridPosition].
+	pub fn get_grid(&self, position: GridPosition) -> T {
+		self.data.get(&position).copied().unwrap_or_default()
+	}
+
+	/// Get the value at the given [WorldPosition].
+	pub fn get(&self, position: WorldPosition) -> T {
+		let grid_position = self.get_grid_position(position);
+		self.get_grid(grid_position)
+	}
+
+	/// Set the value at the given [GridPosition].
+	pub fn set_grid(&mut self, position: GridPosition, value: T) {
+		if let Some(dirty) = &mut self.dirty {
+			if self.data.get(&position).copied().unwrap_or_default() != value {
+				dirty.insert(position);
+			}
+		}
+
+		if value == T::default() {
+			self.data.remove(&position);
+		} else {
+			self.data.insert(position, value);
+		}
+	}
+
+	/// Set the value at the given [WorldPosition].
+	pub fn set(&mut self, position: WorldPosition, value: T) {
+		let grid_position = self.get_grid_position(position);
+		self.set_grid(grid_position, value);
+	}
+
+	/// Starts keeping track of the cells that change, e.g. so that their sprites can be redrawn.
+	///
+	/// Layers are untracked while they are generated, since they are drawn whole afterwards.
+	pub fn track_changes(&mut self) {
+		self.dirty.get_or_insert_with(HashSet::new);
+	}
+
+	/// Whether any cell changed since the changes were last taken.
+	pub fn is_dirty(&self) -> bool {
+		self.dirty.as_ref().is_some_and(|dirty| !dirty.is_empty())
+	}
+
+	/// The chunks of `size` by `size` cells holding the cells changed since the changes were
+	/// last taken.
+	pub fn dirty_chunks(&self, size: i64) -> HashSet<ChunkPosition> {
+		self.dirty.iter().flatten().map(|position| position.chunk(size)).collect()
+	}
+
+	/// Takes the cells changed since the changes were last taken, in no particular order.
+	///
+	/// Setting a cell to the value it already holds does not count as a change.
+	pub fn take_changes(&mut self) -> Vec<GridPosition> {
+		self.dirty.as_mut().map(|dirty| dirty.drain().collect()).unwrap_or_default()
+	}
+
+	/// Get the scale of the layer.
+	pub fn scale(&self) -> i64 {
+		self.scale
+	}
+
+	/// Get the size of the world the layer covers, as a scale factor.
+	pub fn world_size(&self) -> u32 {
+		self.world_size
+	}
+
+	/// Iterates the world position of every cell in the layer.
+	pub fn positions(&self) -> AllGridPositions {
+		AllGridPositions::new(self.scale.trailing_zeros(), self.world_size)
+	}
+
+	/// Whether the given [WorldPosition] lies within the world covered by the layer.
+	pub fn contains(&self, position: WorldPosition) -> bool {
+		let cells_factor = self.world_size.saturating_sub(self.scale.trailing_zeros());
+		if cells_factor >= 63 {
+			return true;
+		}
+
+		let cells = 1i64 << cells_factor;
+		let bounds = -(cells / 2)..cells - cells / 2;
+		let grid_position = self.get_grid_position(position);
+		bounds.contains(&grid_position.x) && bounds.contains(&grid_position.y)
+	}
+
+	/// Gets the values around the given cell, stepping by its cell size rather than this
+	/// layer's, so a fine cell can look across the edges of coarser dependency layers.
+	///
+	/// Cells outside the world are skipped.
+	pub fn neighbors(
+		&self,
+		cell: &WorldCell,
+		neighborhood: Neighborhood,
+	) -> impl Iterator<Item = T> + '_ {
+		let WorldCell { position, cell_size } = *cell;
+		neighborhood
+			.offsets()
+			.into_iter()
+			.filter_map(move |(x, y)| {
+				Some(WorldPosition::new(
+					position.x.checked_add(x.checked_mul(cell_size)?)?,
+					position.y.checked_add(y.checked_mul(cell_size)?)?,
+				))
+			})
+			.filter(|position| self.contains(*position))
+			.map(|position| self.get(position))
+	}
+
+	/// Splits the layer into connected regions of equal values, each listing the world
+	/// positions of its cells starting with the first in row order.
+	pub fn regions(&self, neighborhood: Neighborhood) -> Vec<Vec<WorldPosition>> {
+		self.regions_by(neighborhood, |value| value)
+	}
+
+	/// Splits the layer into connected regions whose values map to equal keys, e.g. to treat
+	/// every kind of water as one body.
+	pub fn regions_by<K: PartialEq>(
+		&self,
+		neighborhood: Neighborhood,
+		key: impl Fn(T) -> K,
+	) -> Vec<Vec<WorldPosition>> {
+		let offsets = neighborhood.offsets();
+		let mut visited = HashSet::new();
+		let mut regions = Vec::new();
+
+		for start in self.positions() {
+			if !visited.insert(start) {
+				continue;
+			}
+
+			let region_key = key(self.get(start));
+			let mut positions = Vec::new();
+			let mut queue = VecDeque::from([start]);
+			while let Some(position) = queue.pop_front() {
+				positions.push(position);
+				for (x, y) in &offsets {
+					let neighbor = WorldPosition::new(
+						position.x + x * self.scale,
+						position.y + y * self.scale,
+					);
+					if self.contains(neighbor)
+						&& key(self.get(neighbor)) == region_key
+						&& visited.insert(neighbor)
+					{
+						queue.push_back(neighbor);
+					}
+				}
+			}
+			regions.push(positions);
+		}
+
+		regions
+	}
+}
+
+/// The largest world size, so that the `1 << world_size` units of a side fit in an [i64].
+pub const MAX_WORLD_SIZE: u32 = 62;
+
+/// An iterator over all grid positions in the layer.
+pub struct AllGridPositions {
+	pub grid_position: GridPosition,
+	pub min: i64,
+	pub max: i64,
+}
+
+impl AllGridPositions {
+	/// Iterates the cells of a base32 layer with the given scale factor over a world of
+	/// `1 << world_size` units per side, centered on the origin.
+	///
+	/// Panics if the world is larger than [MAX_WORLD_SIZE].
+	pub fn new(scale_factor: u32, world_size: u32) -> Self {
+		assert!(world_size <= MAX_WORLD_SIZE, "world size {world_size} exceeds {MAX_WORLD_SIZE}");
+		let scale = 1 << scale_factor;
+		let cells: i64 = 1 << world_size.saturating_sub(scale_factor);
+		let min = -(cells / 2);
+		Self { grid_position: GridPosition::new(min, min, scale), min, max: min + cells }
+	}
+}
+
+impl Iterator for AllGridPositions {
+	type Item = WorldPosition;
+
+	// Walk the grid row by row and convert each cell to its world position.
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.grid_position.y >= self.max {
+			return None;
+		}
+
+		let world_position = self.grid_position.into();
+		self.grid_position.x += 1;
+		if self.grid_position.x >= self.max {
+			self.grid_position.x = self.min;
+			self.grid_position.y += 1;
+		}
+
+		Some(world_position)
+	}
+}
+
+pub fn generate_layer<T: LayerValue, D, F: LayerFactory<T, D>>(
+	scale_factor: u32,
+	world_size: u32,
+	deps: D,
+	factory: F,
+) -> Layer<T> {
+	let mut layer = Layer::new_base32(scale_factor, world_size);
+	for position in layer.positions() {
+		let value = factory.create_value(WorldCell { position, cell_size: layer.scale }, &deps);
+		layer.set(position, value);
+	}
+	layer
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Clone, Copy, Default, PartialEq, Debug)]
+	struct Cell(i64, i64);
+
+	impl LayerValue for Cell {}
+
+	#[test]
+	fn grid_position_floors_negative_coordinates() {
+		let layer = Layer::<Cell>::new_base32(4, MAX_WORLD_SIZE);
+
+		let grid = |x, y| layer.get_grid_position(WorldPosition::new(x, y));
+		assert_eq!(grid(0, 0), GridPosition::new(0, 0, 16));
+		assert_eq!(grid(15, 15), GridPosition::new(0, 0, 16));
+		assert_eq!(grid(-1, -1), GridPosition::new(-1, -1, 16));
+		assert_eq!(grid(-16, 16), GridPosition::new(-1, 1, 16));
+		assert_eq!(grid(-17, -32), GridPosition::new(-2, -2, 16));
+		assert_eq!(grid(i64::MIN, i64::MAX), GridPosition::new(-(1 << 59), (1 << 59) - 1, 16));
+	}
+
+	#[test]
+	fn cells_are_equally_wide_across_the_origin() {
+		let layer = Layer::<Cell>::new_base32(4, MAX_WORLD_SIZE);
+
+		let mut widths = HashMap::new();
+		for x in -64..64 {
+			let grid_position = layer.get_grid_position(WorldPosition::new(x, 0));
+			*widths.entry(grid_position.x).or_insert(0) += 1;
+		}
+
+		assert_eq!(widths.len(), 8);
+		assert!(widths.values().all(|width| *width == 16));
+	}
+
+	#[test]
+	#[should_panic(expected = "world size 63 exceeds 62")]
+	fn worlds_too_large_to_count_are_refused() {
+		Layer::<Cell>::new_base32(4, MAX_WORLD_SIZE + 1);
+	}
+
+	#[test]
+	fn layer_lookups_are_continuous_across_the_origin() {
+		let mut layer = Layer::new_base32(2, 5);
+		for position in layer.positions() {
+			layer.set(position, Cell(position.x, position.y));
+		}
+
+		for x in -16..16 {
+			for y in -16..16 {
+				let value = layer.get(WorldPosition::new(x, y));
+				assert_eq!(value, Cell(x.div_euclid(4) * 4, y.div_euclid(4) * 4));
+			}
+		}
+	}
+
+	#[test]
+	fn all_grid_positions_are_centered_on_the_origin() {
+		let positions: Vec<_> = AllGridPositions::new(2, 4).collect();
+
+		assert_eq!(positions.len(), 16);
+		assert_eq!(positions.first(), Some(&WorldPosition::new(-8, -8)));
+		assert_eq!(positions.last(), Some(&WorldPosition::new(4, 4)));
+	}
+
+	#[test]
+	fn all_grid_positions_span_the_largest_world() {
+		let positions: Vec<_> = AllGridPositions::new(MAX_WORLD_SIZE - 4, MAX_WORLD_SIZE).collect();
+
+		assert_eq!(positions.len(), 16 * 16);
+		assert_eq!(positions.first(), Some(&WorldPosition::new(-(1 << 61), -(1 << 61))));
+		assert_eq!(positions.last(), Some(&WorldPosition::new(7 << 58, 7 << 58)));
+	}
+
+	#[test]
+	fn neighborhood_offsets_exclude_the_center() {
+		assert_eq!(Neighborhood::VonNeumann.offsets().len(), 4);
+		assert_eq!(Neighborhood::Moore.offsets().len(), 8);
+		assert_eq!(Neighborhood::Radius(2).offsets().len(), 12);
+		assert!(!Neighborhood::Radius(3).offsets().contains(&(0, 0)));
+	}
+
+	#[test]
+	fn neighbors_skip_cells_outside_the_world() {
+		let layer = Layer::<Cell>::new_base32(2, 4);
+
+		let corner = WorldCell { position: WorldPosition::new(-8, -8), cell_size: 4 };
+		assert_eq!(layer.neighbors(&corner, Neighborhood::Moore).count(), 3);
+
+		let edge = WorldCell { position: WorldPosition::new(0, 4), cell_size: 4 };
+		assert_eq!(layer.neighbors(&edge, Neighborhood::VonNeumann).count(), 3);
+	}
+
+	#[test]
+	fn neighbors_step_by_the_queried_cell_size() {
+		let mut coarse = Layer::new_base32(4, 6);
+		for position in coarse.positions() {
+			coarse.set(position, Cell(position.x, position.y));
+		}
+
+		// A fine cell in the middle of a coarse cell only sees that coarse cell, while one on its
+		// edge also sees the coarse cell across the edge.
+		let inner = WorldCell { position: WorldPosition::new(4, 4), cell_size: 4 };
+		assert!(coarse.neighbors(&inner, Neighborhood::Moore).all(|value| value == Cell(0, 0)));
+
+		let edge = WorldCell { position: WorldPosition::new(12, 4), cell_size: 4 };
+		assert!(coarse
+			.neighbors(&edge, Neighborhood::VonNeumann)
+			.any(|value| value == Cell(16, 0)));
+	}
+
+	#[test]
+	fn tracked_layers_record_changed_cells_and_chunks() {
+		let mut layer = Layer::new_base32(2, 6);
+		layer.set(WorldPosition::new(0, 0), Cell(1, 1));
+		assert!(!layer.is_dirty(), "untracked layers record nothing");
+
+		layer.track_changes();
+		layer.set(WorldPosition::new(1, 1), Cell(1, 1));
+		assert!(!layer.is_dirty(), "setting the same value is no change");
+
+		layer.set(WorldPosition::new(-1, 5), Cell(2, 2));
+		layer.set(WorldPosition::new(0, 0), Cell::default());
+		assert_eq!(
+			layer.dirty_chunks(2),
+			HashSet::from([
+				ChunkPosition { x: -1, y: 0, size: 2 },
+				ChunkPosition { x: 0, y: 0, size: 2 },
+			])
+		);
+
+		let mut changes = layer.take_changes();
+		changes.sort_by_key(|position| (position.x, position.y));
+		assert_eq!(changes, vec![GridPosition::new(-1, 1, 4), GridPosition::new(0, 0, 4)]);
+		assert!(!layer.is_dirty());
+		assert!(layer.take_changes().is_empty());
+	}
+}
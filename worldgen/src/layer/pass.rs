@@ -0,0 +1,295 @@
+use crate::layer::{Layer, LayerValue, Neighborhood, WorldCell, WorldPosition};
+
+/// A post-processing pass that rewrites a generated layer.
+pub trait LayerPass<T: LayerValue> {
+	fn apply(&self, layer: &Layer<T>) -> Layer<T>;
+}
+
+impl<T: LayerValue> Layer<T> {
+	/// Runs the given [LayerPass] over the layer, so passes can be chained after generation.
+	pub fn apply(self, pass: impl LayerPass<T>) -> Layer<T> {
+		pass.apply(&self)
+	}
+
+	/// The cell of this layer's size at the given position.
+	fn cell(&self, position: WorldPosition) -> WorldCell {
+		WorldCell { position, cell_size: self.scale }
+	}
+
+	/// Rebuilds the layer by mapping every cell, reading only from the current layer.
+	fn rewrite(&self, f: impl Fn(WorldPosition, T) -> T) -> Layer<T> {
+		let mut layer = Layer::new_base32(self.scale.trailing_zeros(), self.world_size);
+		for position in self.positions() {
+			layer.set(position, f(position, self.get(position)));
+		}
+		layer
+	}
+}
+
+/// Gets the most common of the given values, preferring `current` and then the earliest value
+/// on ties.
+pub(super) fn most_common<T: LayerValue>(
+	values: impl Iterator<Item = T>,
+	current: Option<T>,
+) -> Option<T> {
+	let mut counts: Vec<(T, usize)> = Vec::new();
+	for value in values {
+		match counts.iter_mut().find(|(counted, _)| *counted == value) {
+			Some((_, count)) => *count += 1,
+			None => counts.push((value, 1)),
+		}
+	}
+
+	let max = counts.iter().map(|(_, count)| *count).max()?;
+	let mut tied = counts.into_iter().filter(|(_, count)| *count == max).map(|(value, _)| value);
+	let first = tied.next()?;
+	match current {
+		Some(current) if current == first || tied.any(|value| value == current) => Some(current),
+		_ => Some(first),
+	}
+}
+
+/// Replaces every cell with the most common value among itself and its neighbors.
+#[derive(Clone, Copy, Debug)]
+pub struct MajorityFilter {
+	pub neighborhood: Neighborhood,
+	pub iterations: usize,
+}
+
+impl<T: LayerValue> LayerPass<T> for MajorityFilter {
+	fn apply(&self, layer: &Layer<T>) -> Layer<T> {
+		let mut layer = layer.clone();
+		for _ in 0..self.iterations {
+			layer = layer.rewrite(|position, value| {
+				let neighbors = layer.neighbors(&layer.cell(position), self.neighborhood);
+				most_common(std::iter::once(value).chain(neighbors), Some(value)).unwrap_or(value)
+			});
+		}
+		layer
+	}
+}
+
+/// Shrinks regions of `value` by replacing cells on their border with the most common
+/// neighboring value.
+#[derive(Clone, Copy, Debug)]
+pub struct Erode<T: LayerValue> {
+	pub value: T,
+	pub neighborhood: Neighborhood,
+	pub iterations: usize,
+}
+
+impl<T: LayerValue> LayerPass<T> for Erode<T> {
+	fn apply(&self, layer: &Layer<T>) -> Layer<T> {
+		let mut layer = layer.clone();
+		for _ in 0..self.iterations {
+			layer = layer.rewrite(|position, value| {
+				if value != self.value {
+					return value;
+				}
+				let neighbors = layer.neighbors(&layer.cell(position), self.neighborhood);
+				most_common(neighbors.filter(|neighbor| *neighbor != self.value), None)
+					.unwrap_or(value)
+			});
+		}
+		layer
+	}
+}
+
+/// Grows regions of `value` into every cell that neighbors them.
+#[derive(Clone, Copy, Debug)]
+pub struct Dilate<T: LayerValue> {
+	pub value: T,
+	pub neighborhood: Neighborhood,
+	pub iterations: usize,
+}
+
+impl<T: LayerValue> LayerPass<T> for Dilate<T> {
+	fn apply(&self, layer: &Layer<T>) -> Layer<T> {
+		let mut layer = layer.clone();
+		for _ in 0..self.iterations {
+			layer = layer.rewrite(|position, value| {
+				let mut neighbors = layer.neighbors(&layer.cell(position), self.neighborhood);
+				if neighbors.any(|neighbor| neighbor == self.value) {
+					self.value
+				} else {
+					value
+				}
+			});
+		}
+		layer
+	}
+}
+
+/// Replaces every connected region smaller than `min_size` cells with the most common value
+/// bordering it.
+#[derive(Clone, Copy, Debug)]
+pub struct RemoveSmallRegions {
+	pub min_size: usize,
+	pub neighborhood: Neighborhood,
+}
+
+impl<T: LayerValue> LayerPass<T> for RemoveSmallRegions {
+	fn apply(&self, layer: &Layer<T>) -> Layer<T> {
+		let mut result = layer.clone();
+		for region in layer.regions(self.neighborhood) {
+			if region.len() >= self.min_size {
+				continue;
+			}
+
+			let value = layer.get(region[0]);
+			let border = region.iter().flat_map(|position| {
+				layer
+					.neighbors(&layer.cell(*position), self.neighborhood)
+					.filter(|neighbor| *neighbor != value)
+			});
+			if let Some(replacement) = most_common(border, None) {
+				for position in region {
+					result.set(position, replacement);
+				}
+			}
+		}
+		result
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Clone, Copy, PartialEq, Debug)]
+	struct Tile(u8);
+
+	impl Default for Tile {
+		fn default() -> Self {
+			Tile(b'.')
+		}
+	}
+
+	impl LayerValue for Tile {}
+
+	/// Builds an 8x8 layer of unit cells from rows of characters, top row first.
+	fn grid(rows: [&str; 8]) -> Layer<Tile> {
+		let mut layer = Layer::new_base32(0, 3);
+		for (y, row) in rows.iter().enumerate() {
+			for (x, tile) in row.bytes().enumerate() {
+				layer.set(WorldPosition::new(x as i64 - 4, 3 - y as i64), Tile(tile));
+			}
+		}
+		layer
+	}
+
+	/// Renders the layer back into rows of characters, top row first.
+	fn rows(layer: &Layer<Tile>) -> Vec<String> {
+		(0..8)
+			.map(|y| {
+				(0..8).map(|x| layer.get(WorldPosition::new(x - 4, 3 - y)).0 as char).collect()
+			})
+			.collect()
+	}
+
+	#[test]
+	fn majority_filter_removes_single_cell_islands() {
+		let layer = grid([
+			"........", "..#.....", "........", "....###.", "....###.", "....###.", ".#......",
+			"........",
+		]);
+
+		let smoothed =
+			layer.apply(MajorityFilter { neighborhood: Neighborhood::Moore, iterations: 1 });
+
+		assert_eq!(
+			rows(&smoothed),
+			[
+				"........", "........", "........", ".....#..", "....###.", ".....#..", "........",
+				"........",
+			]
+		);
+	}
+
+	#[test]
+	fn majority_filter_keeps_a_stable_layer() {
+		let layer = grid([
+			"########", "########", "########", "########", "........", "........", "........",
+			"........",
+		]);
+
+		let smoothed = layer
+			.clone()
+			.apply(MajorityFilter { neighborhood: Neighborhood::Moore, iterations: 3 });
+
+		assert_eq!(rows(&smoothed), rows(&layer));
+	}
+
+	#[test]
+	fn erode_then_dilate_opens_thin_features() {
+		let layer = grid([
+			"........", ".###....", ".###....", ".###....", "......#.", "......#.", "......#.",
+			"........",
+		]);
+
+		let eroded = layer.apply(Erode {
+			value: Tile(b'#'),
+			neighborhood: Neighborhood::VonNeumann,
+			iterations: 1,
+		});
+		assert_eq!(
+			rows(&eroded),
+			[
+				"........", "........", "..#.....", "........", "........", "........", "........",
+				"........",
+			]
+		);
+
+		let dilated = eroded.apply(Dilate {
+			value: Tile(b'#'),
+			neighborhood: Neighborhood::Moore,
+			iterations: 1,
+		});
+		assert_eq!(
+			rows(&dilated),
+			[
+				"........", ".###....", ".###....", ".###....", "........", "........", "........",
+				"........",
+			]
+		);
+	}
+
+	#[test]
+	fn remove_small_regions_replaces_them_with_their_surroundings() {
+		let layer = grid([
+			"~~~~....", "~~~~....", "~~~~..~.", "~~~~....", "........", ".###....", ".#.#....",
+			".###....",
+		]);
+
+		let cleaned =
+			layer.apply(RemoveSmallRegions { min_size: 4, neighborhood: Neighborhood::VonNeumann });
+
+		assert_eq!(
+			rows(&cleaned),
+			[
+				"~~~~....", "~~~~....", "~~~~....", "~~~~....", "........", ".###....", ".###....",
+				".###....",
+			]
+		);
+	}
+
+	#[test]
+	fn passes_chain() {
+		let layer = grid([
+			"........", "..#.....", "........", "....###.", "....###.", "....###.", ".#......",
+			"........",
+		]);
+
+		let smoothed = layer
+			.apply(RemoveSmallRegions { min_size: 2, neighborhood: Neighborhood::Moore })
+			.apply(Erode { value: Tile(b'#'), neighborhood: Neighborhood::Moore, iterations: 1 });
+
+		assert_eq!(
+			rows(&smoothed),
+			[
+				"........", "........", "........", "........", ".....#..", "........", "........",
+				"........",
+			]
+		);
+	}
+}
@@ -0,0 +1,308 @@
+use crate::layer::base::{NoiseGenerator, NoiseStream};
+use crate::layer::region::RegionId;
+use crate::layer::{GridPosition, Layer, LayerValue, WorldCell, WorldPosition};
+use rand::prelude::*;
+use std::collections::HashMap;
+
+/// Whether a site may be placed at a cell.
+type Predicate<'a> = Box<dyn Fn(WorldCell) -> bool + 'a>;
+
+/// Which region a position belongs to, for capping the sites per region.
+type Regions<'a> = Box<dyn Fn(WorldPosition) -> Option<RegionId> + 'a>;
+
+/// How sites of one value are spread over a layer.
+pub struct SiteRule<'a, T> {
+	pub value: T,
+	/// The least distance between two sites of the value, in cells.
+	pub min_distance: f64,
+	/// The most sites of the value within one region, if capped.
+	pub max_per_region: Option<usize>,
+	predicate: Predicate<'a>,
+}
+
+impl<'a, T: LayerValue> SiteRule<'a, T> {
+	/// Creates a rule placing the value on any free cell, at least `min_distance` cells from
+	/// other sites of the value.
+	pub fn new(value: T, min_distance: f64) -> Self {
+		Self { value, min_distance, max_per_region: None, predicate: Box::new(|_| true) }
+	}
+
+	pub fn max_per_region(mut self, max: usize) -> Self {
+		self.max_per_region = Some(max);
+		self
+	}
+
+	/// Only places the value on cells passing the predicate, e.g. mountains of a coarser layer.
+	pub fn only_where(mut self, predicate: impl Fn(WorldCell) -> bool + 'a) -> Self {
+		self.predicate = Box::new(predicate);
+		self
+	}
+}
+
+/// Scatters rare sites over a layer with Poisson-disk sampling, as an alternative to a
+/// [crate::layer::LayerFactory] thresholding noise per cell.
+///
+/// Candidate cells are visited in a seeded random order and accepted unless they are too close
+/// to a site of the same value, which spreads sites out evenly instead of in random clumps.
+pub struct SitePlacer<'a, T: LayerValue> {
+	noise_gen: NoiseGenerator,
+	stream: NoiseStream,
+	rules: Vec<SiteRule<'a, T>>,
+	regions: Regions<'a>,
+}
+
+impl<'a, T: LayerValue> SitePlacer<'a, T> {
+	/// Creates a placer without any rules, where the whole world is one region, shuffling its
+	/// candidates with the given stream so that no two placers visit cells in the same order.
+	pub fn new(noise_gen: NoiseGenerator, stream: NoiseStream) -> Self {
+		Self { noise_gen, stream, rules: Vec::new(), regions: Box::new(|_| None) }
+	}
+
+	/// Sets the regions that [SiteRule::max_per_region] counts sites in, e.g. landmasses.
+	pub fn with_regions(
+		mut self,
+		regions: impl Fn(WorldPosition) -> Option<RegionId> + 'a,
+	) -> Self {
+		self.regions = Box::new(regions);
+		self
+	}
+
+	/// Adds a rule, where earlier rules pick their cells first.
+	pub fn rule(mut self, rule: SiteRule<'a, T>) -> Self {
+		self.rules.push(rule);
+		self
+	}
+
+	/// Places the sites of every rule on free cells of the layer.
+	///
+	/// Sites already in the layer are kept, and count towards the spacing and caps of their
+	/// value, unless they lie outside the world.
+	pub fn place(&self, layer: &mut Layer<T>) {
+		let cell_size = layer.scale();
+		let Some(first) = layer.positions().next() else {
+			return;
+		};
+		let first = layer.get_grid_position(first);
+		let origin = (first.x, first.y);
+		// The same number of cells per side as the layer's positions, which is at least one.
+		let side: i64 = 1 << layer.world_size().saturating_sub(cell_size.trailing_zeros());
+		let cell_at = |index: usize| {
+			let index = index as i64;
+			GridPosition::new(origin.0 + index % side, origin.1 + index / side, cell_size)
+		};
+		let index_of =
+			|cell: &GridPosition| ((cell.y - origin.1) * side + cell.x - origin.0) as usize;
+		// Tracks the free cells apart from the layer, which is slower to query.
+		let mut occupied = vec![false; (side * side) as usize];
+		let inside = move |cell: &GridPosition| {
+			(0..side).contains(&(cell.x - origin.0)) && (0..side).contains(&(cell.y - origin.1))
+		};
+		for cell in layer.data.keys().filter(|cell| inside(cell)) {
+			occupied[index_of(cell)] = true;
+		}
+
+		for (index, rule) in self.rules.iter().enumerate() {
+			let mut sites = SpatialGrid::new(rule.min_distance, origin, side);
+			let mut counts: HashMap<Option<RegionId>, usize> = HashMap::new();
+			for (grid_position, _) in
+				layer.data.iter().filter(|(cell, value)| **value == rule.value && inside(cell))
+			{
+				sites.insert((grid_position.x, grid_position.y));
+				*counts.entry((self.regions)((*grid_position).into())).or_default() += 1;
+			}
+
+			let seed = self
+				.noise_gen
+				.get_noise_value(&WorldPosition::new(index as i64, 0), self.stream);
+			let mut candidates: Vec<u32> = (0..occupied.len() as u32).collect();
+			candidates.shuffle(&mut StdRng::seed_from_u64(seed as u64));
+
+			for candidate in candidates {
+				let grid_position = cell_at(candidate as usize);
+				let position = WorldPosition::from(grid_position);
+				let site = (grid_position.x, grid_position.y);
+				// The checks that don't query other layers go first, as they are much faster.
+				if occupied[candidate as usize]
+					|| sites.is_crowded(site)
+					|| !(rule.predicate)(WorldCell { position, cell_size })
+				{
+					continue;
+				}
+				let region = (self.regions)(position);
+				let count = counts.get(&region).copied().unwrap_or_default();
+				if rule.max_per_region.is_some_and(|max| count >= max) {
+					continue;
+				}
+
+				sites.insert(site);
+				*counts.entry(region).or_default() += 1;
+				occupied[candidate as usize] = true;
+				layer.set_grid(grid_position, rule.value);
+			}
+		}
+	}
+}
+
+/// The sites of one value, bucketed by the minimum distance so only nearby buckets are checked.
+struct SpatialGrid {
+	min_distance: f64,
+	bucket_size: i64,
+	/// The lowest cell of the layer, in grid coordinates.
+	origin: (i64, i64),
+	/// The number of buckets per side.
+	buckets_per_side: i64,
+	buckets: Vec<Vec<(i64, i64)>>,
+}
+
+impl SpatialGrid {
+	/// Creates an empty grid over a square layer of `side` cells, starting at `origin`.
+	fn new(min_distance: f64, origin: (i64, i64), side: i64) -> Self {
+		let bucket_size = min_distance.ceil().max(1.0) as i64;
+		let buckets_per_side = (side + bucket_size - 1) / bucket_size;
+		Self {
+			min_distance,
+			bucket_size,
+			origin,
+			buckets_per_side,
+			buckets: vec![Vec::new(); (buckets_per_side * buckets_per_side) as usize],
+		}
+	}
+
+	fn bucket(&self, (x, y): (i64, i64)) -> (i64, i64) {
+		((x - self.origin.0) / self.bucket_size, (y - self.origin.1) / self.bucket_size)
+	}
+
+	fn insert(&mut self, site: (i64, i64)) {
+		let (x, y) = self.bucket(site);
+		self.buckets[(y * self.buckets_per_side + x) as usize].push(site);
+	}
+
+	/// Whether a site lies closer than the minimum distance to the given one.
+	fn is_crowded(&self, (x, y): (i64, i64)) -> bool {
+		let (bucket_x, bucket_y) = self.bucket((x, y));
+		let range = |bucket: i64| bucket.max(1) - 1..=(bucket + 1).min(self.buckets_per_side - 1);
+		range(bucket_y).any(|bucket_y| {
+			range(bucket_x).any(|bucket_x| {
+				self.buckets[(bucket_y * self.buckets_per_side + bucket_x) as usize].iter().any(
+					|(site_x, site_y)| {
+						(((site_x - x).pow(2) + (site_y - y).pow(2)) as f64).sqrt()
+							< self.min_distance
+					},
+				)
+			})
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::layer::base::streams;
+
+	#[derive(Clone, Copy, Debug, Default, PartialEq)]
+	enum Site {
+		#[default]
+		None,
+		Tower,
+		Well,
+	}
+
+	impl LayerValue for Site {}
+
+	fn sites(layer: &Layer<Site>, site: Site) -> Vec<WorldPosition> {
+		layer.positions().filter(|position| layer.get(*position) == site).collect()
+	}
+
+	#[test]
+	fn sites_keep_their_distance() {
+		let mut layer = Layer::new_base32(0, 5);
+		SitePlacer::new(NoiseGenerator::new(1), streams::SPECIAL)
+			.rule(SiteRule::new(Site::Tower, 5.0))
+			.place(&mut layer);
+
+		let towers = sites(&layer, Site::Tower);
+		// A 32x32 grid packs far more than a dozen sites five cells apart.
+		assert!(towers.len() > 12, "only {} towers", towers.len());
+		for (index, a) in towers.iter().enumerate() {
+			for b in &towers[index + 1..] {
+				let distance = (((a.x - b.x).pow(2) + (a.y - b.y).pow(2)) as f64).sqrt();
+				assert!(distance >= 5.0, "towers at {a:?} and {b:?}");
+			}
+		}
+	}
+
+	#[test]
+	fn sites_are_capped_per_region() {
+		let mut layer = Layer::new_base32(0, 4);
+		SitePlacer::new(NoiseGenerator::new(2), streams::SPECIAL)
+			.with_regions(|position| Some(RegionId(u32::from(position.x >= 0))))
+			.rule(SiteRule::new(Site::Tower, 1.0).max_per_region(2))
+			.place(&mut layer);
+
+		let towers = sites(&layer, Site::Tower);
+		assert_eq!(towers.iter().filter(|position| position.x < 0).count(), 2);
+		assert_eq!(towers.iter().filter(|position| position.x >= 0).count(), 2);
+	}
+
+	#[test]
+	fn predicates_and_existing_sites_are_respected() {
+		let mut layer = Layer::new_base32(0, 4);
+		let well = WorldPosition::new(-3, 2);
+		layer.set(well, Site::Well);
+
+		SitePlacer::new(NoiseGenerator::new(3), streams::SPECIAL)
+			.rule(SiteRule::new(Site::Well, 4.0).only_where(|cell| cell.position.x < 0))
+			.rule(SiteRule::new(Site::Tower, 0.0).only_where(|cell| cell.position.y == 2))
+			.place(&mut layer);
+
+		let wells = sites(&layer, Site::Well);
+		assert!(wells.contains(&well));
+		assert!(wells.iter().all(|position| position.x < 0));
+		assert!(wells.iter().filter(|position| **position != well).all(|position| (position.x
+			- well.x)
+			.pow(2)
+			+ (position.y - well.y).pow(2)
+			>= 16));
+		// Towers fill the rest of the row, but never replace a well.
+		assert!(sites(&layer, Site::Tower).iter().all(|position| position.y == 2));
+		assert_eq!(layer.get(well), Site::Well);
+	}
+
+	#[test]
+	fn sites_outside_the_world_are_ignored() {
+		let mut layer = Layer::new_base32(0, 4);
+		let outside = WorldPosition::new(100, 0);
+		layer.set(outside, Site::Tower);
+		SitePlacer::new(NoiseGenerator::new(1), streams::SPECIAL)
+			.rule(SiteRule::new(Site::Tower, 3.0))
+			.place(&mut layer);
+
+		assert_eq!(layer.get(outside), Site::Tower);
+		assert!(!sites(&layer, Site::Tower).is_empty());
+	}
+
+	#[test]
+	fn cells_larger_than_the_world_hold_a_site() {
+		let mut layer = Layer::new_base32(6, 4);
+		SitePlacer::new(NoiseGenerator::new(1), streams::SPECIAL)
+			.rule(SiteRule::new(Site::Tower, 2.0))
+			.place(&mut layer);
+
+		assert_eq!(sites(&layer, Site::Tower).len(), 1);
+	}
+
+	#[test]
+	fn placement_is_deterministic() {
+		let place = |seed| {
+			let mut layer = Layer::new_base32(0, 5);
+			SitePlacer::new(NoiseGenerator::new(seed), streams::SPECIAL)
+				.rule(SiteRule::new(Site::Tower, 3.0))
+				.rule(SiteRule::new(Site::Well, 6.0))
+				.place(&mut layer);
+			(sites(&layer, Site::Tower), sites(&layer, Site::Well))
+		};
+
+		assert_eq!(place(4), place(4));
+		assert_ne!(place(4), place(5));
+	}
+}
@@ -0,0 +1,218 @@
+use crate::layer::{GridPosition, Layer, LayerValue, Neighborhood, WorldPosition};
+use std::collections::{BTreeSet, HashMap};
+
+/// Identifies a region within its [RegionMap].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RegionId(pub u32);
+
+/// A connected region of cells sharing one key, such as a continent, a lake or a biome patch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region<K> {
+	pub id: RegionId,
+	pub key: K,
+	/// The number of cells in the region.
+	pub cells: usize,
+	/// The lowest corner of the bounding box, inclusive.
+	pub min: WorldPosition,
+	/// The highest corner of the bounding box, exclusive.
+	pub max: WorldPosition,
+	/// The mean of the centers of the cells, in world units.
+	pub centroid: (f64, f64),
+	/// The regions sharing a border with this one.
+	pub neighbors: BTreeSet<RegionId>,
+}
+
+impl<K> Region<K> {
+	/// The area of the region in square world units, saturating for the largest worlds.
+	pub fn area(&self, cell_size: i64) -> u128 {
+		let cell_area = (cell_size as u128).saturating_mul(cell_size as u128);
+		(self.cells as u128).saturating_mul(cell_area)
+	}
+}
+
+/// The connected regions of a layer, queryable by [WorldPosition].
+#[derive(Clone, Debug)]
+pub struct RegionMap<K> {
+	regions: Vec<Region<K>>,
+	ids: HashMap<GridPosition, RegionId>,
+	cell_size: i64,
+}
+
+impl<K: Copy + PartialEq> RegionMap<K> {
+	/// Labels the regions of the layer, where connected cells whose values map to equal keys
+	/// form one region.
+	pub fn new<T: LayerValue>(
+		layer: &Layer<T>,
+		neighborhood: Neighborhood,
+		key: impl Fn(T) -> K,
+	) -> Self {
+		let cell_size = layer.scale();
+		let mut regions = Vec::new();
+		let mut ids = HashMap::new();
+
+		for (index, positions) in layer.regions_by(neighborhood, &key).into_iter().enumerate() {
+			let id = RegionId(index as u32);
+			let (mut min, mut max) = (positions[0], positions[0]);
+			let (mut sum_x, mut sum_y) = (0.0, 0.0);
+			for position in &positions {
+				ids.insert(layer.get_grid_position(*position), id);
+				min = WorldPosition::new(min.x.min(position.x), min.y.min(position.y));
+				max = WorldPosition::new(max.x.max(position.x), max.y.max(position.y));
+				sum_x += position.x as f64 + cell_size as f64 / 2.0;
+				sum_y += position.y as f64 + cell_size as f64 / 2.0;
+			}
+
+			regions.push(Region {
+				id,
+				key: key(layer.get(positions[0])),
+				cells: positions.len(),
+				min,
+				max: WorldPosition::new(
+					max.x.saturating_add(cell_size),
+					max.y.saturating_add(cell_size),
+				),
+				centroid: (sum_x / positions.len() as f64, sum_y / positions.len() as f64),
+				neighbors: BTreeSet::new(),
+			});
+		}
+
+		// Regions touch wherever a cell neighbors a cell of another region.
+		let offsets = neighborhood.offsets();
+		for (grid_position, id) in &ids {
+			for (x, y) in &offsets {
+				let neighbor =
+					GridPosition::new(grid_position.x + x, grid_position.y + y, cell_size);
+				if let Some(other) = ids.get(&neighbor).filter(|other| *other != id) {
+					regions[id.0 as usize].neighbors.insert(*other);
+				}
+			}
+		}
+
+		Self { regions, ids, cell_size }
+	}
+
+	/// Gets the region covering the given position, if it lies within the world.
+	pub fn region_at(&self, position: WorldPosition) -> Option<&Region<K>> {
+		let grid_position = GridPosition::new(
+			position.x.div_euclid(self.cell_size),
+			position.y.div_euclid(self.cell_size),
+			self.cell_size,
+		);
+		self.ids.get(&grid_position).map(|id| self.get(*id))
+	}
+
+	pub fn get(&self, id: RegionId) -> &Region<K> {
+		&self.regions[id.0 as usize]
+	}
+
+	/// Iterates every region, ordered by ID.
+	pub fn iter(&self) -> impl Iterator<Item = &Region<K>> {
+		self.regions.iter()
+	}
+
+	/// Iterates the regions with the given key.
+	pub fn with_key(&self, key: K) -> impl Iterator<Item = &Region<K>> {
+		self.regions.iter().filter(move |region| region.key == key)
+	}
+
+	/// Iterates the regions bordering the given one.
+	pub fn neighbors(&self, id: RegionId) -> impl Iterator<Item = &Region<K>> {
+		self.get(id).neighbors.iter().map(|id| self.get(*id))
+	}
+
+	pub fn len(&self) -> usize {
+		self.regions.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.regions.is_empty()
+	}
+
+	/// The cell size of the labeled layer.
+	pub fn cell_size(&self) -> i64 {
+		self.cell_size
+	}
+}
+
+impl<T: LayerValue> RegionMap<T> {
+	/// Labels the orthogonally connected regions of equal values, e.g. the biome patches of a
+	/// biome layer.
+	pub fn of(layer: &Layer<T>) -> Self {
+		Self::new(layer, Neighborhood::VonNeumann, |value| value)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::layer::layers::water::{Landmass, WaterType};
+
+	/// An 8x8 water layer with an island in the ocean holding a lake, and a separate islet.
+	fn islands() -> Layer<WaterType> {
+		let rows = [
+			"~~~~~~~~", "~####~~~", "~#ll#~~~", "~####~~~", "~~~~~~#~", "~~~~~~~~", "~~~~~~~~",
+			"~~~~~~~~",
+		];
+		let mut layer = Layer::new_base32(1, 4);
+		for (y, row) in rows.iter().enumerate() {
+			for (x, tile) in row.chars().enumerate() {
+				let water = match tile {
+					'~' => WaterType::Ocean,
+					'l' => WaterType::Lake,
+					_ => WaterType::None,
+				};
+				layer.set(WorldPosition::new(x as i64 * 2 - 8, 6 - y as i64 * 2), water);
+			}
+		}
+		layer
+	}
+
+	#[test]
+	fn regions_are_labeled_with_areas_and_bounds() {
+		let regions = RegionMap::of(&islands());
+
+		assert_eq!(regions.len(), 4);
+		let lake = regions.with_key(WaterType::Lake).next().unwrap();
+		assert_eq!(lake.cells, 2);
+		assert_eq!(lake.area(regions.cell_size()), 8);
+		assert_eq!(lake.min, WorldPosition::new(-4, 2));
+		assert_eq!(lake.max, WorldPosition::new(0, 4));
+		assert_eq!(lake.centroid, (-2.0, 3.0));
+	}
+
+	#[test]
+	fn regions_are_queryable_by_position() {
+		let regions = RegionMap::of(&islands());
+
+		let lake = regions.with_key(WaterType::Lake).next().unwrap();
+		assert_eq!(regions.region_at(WorldPosition::new(-3, 3)).unwrap().id, lake.id);
+		assert_eq!(regions.region_at(WorldPosition::new(-8, -8)).unwrap().key, WaterType::Ocean);
+		assert!(regions.region_at(WorldPosition::new(8, 0)).is_none());
+	}
+
+	#[test]
+	fn adjacent_regions_are_linked() {
+		let regions = RegionMap::of(&islands());
+
+		let lake = regions.with_key(WaterType::Lake).next().unwrap();
+		let shores: Vec<_> = regions.neighbors(lake.id).collect();
+		assert_eq!(shores.len(), 1);
+		assert_eq!(shores[0].key, WaterType::None);
+		assert_eq!(shores[0].cells, 10);
+
+		let ocean = regions.with_key(WaterType::Ocean).next().unwrap();
+		assert_eq!(ocean.neighbors.len(), 2);
+		assert!(!ocean.neighbors.contains(&lake.id));
+	}
+
+	#[test]
+	fn landmasses_include_their_lakes() {
+		let landmasses = islands().landmasses();
+
+		let mut sizes: Vec<_> =
+			landmasses.with_key(Landmass::Land).map(|region| region.cells).collect();
+		sizes.sort_unstable();
+		assert_eq!(sizes, [1, 12]);
+		assert_eq!(landmasses.with_key(Landmass::Ocean).count(), 1);
+	}
+}
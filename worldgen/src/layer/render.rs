@@ -0,0 +1,111 @@
+use crate::layer::{GridPosition, Layer, LayerValue, WorldCell};
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+/// A [LayerValue] that can be rendered to a cell.
+pub trait LayerRender: LayerValue {
+	/// Spawns the sprite for the value, returning it unless the value is not drawn.
+	fn render(&self, commands: &mut Commands, world_cell: &WorldCell) -> Option<Entity>;
+	fn get_color(&self) -> Color;
+}
+
+impl WorldCell {
+	/// The size of the cell when drawn, at one pixel per world unit.
+	pub fn size(&self) -> Vec2 {
+		Vec2::splat(self.cell_size as f32)
+	}
+
+	/// The transform placing a sprite at the center of the cell, at one pixel per world unit.
+	pub fn transform(&self) -> Transform {
+		let half = self.cell_size as f32 / 2.0;
+		Transform::from_xyz(self.position.x as f32 + half, self.position.y as f32 + half, 0.0)
+	}
+}
+
+impl<T: LayerRender> Layer<T> {
+	/// Render the layer to the given [Commands], returning the spawned sprites.
+	pub fn render(&self, commands: &mut Commands) -> Vec<Entity> {
+		self.data
+			.iter()
+			.filter_map(|(position, value)| {
+				let world_position = (*position).into();
+				value.render(
+					commands,
+					&WorldCell { position: world_position, cell_size: self.scale },
+				)
+			})
+			.collect()
+	}
+}
+
+/// The sprites drawn for the cells of a layer, so that the cells can be redrawn one at a time as
+/// they change instead of the whole layer.
+#[derive(Clone, Debug, Default)]
+pub struct CellSprites {
+	sprites: HashMap<GridPosition, Entity>,
+}
+
+impl CellSprites {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// The sprite drawn for the cell, if it is drawn.
+	pub fn get(&self, position: GridPosition) -> Option<Entity> {
+		self.sprites.get(&position).copied()
+	}
+
+	/// Every sprite drawn.
+	pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+		self.sprites.values().copied()
+	}
+
+	pub fn len(&self) -> usize {
+		self.sprites.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.sprites.is_empty()
+	}
+
+	/// Despawns every sprite.
+	pub fn clear(&mut self, commands: &mut Commands) {
+		for (_, entity) in self.sprites.drain() {
+			commands.entity(entity).despawn();
+		}
+	}
+}
+
+impl<T: LayerValue> Layer<T> {
+	/// Draws every cell with `draw`, replacing whatever the sprites held before.
+	pub fn draw(
+		&self,
+		commands: &mut Commands,
+		sprites: &mut CellSprites,
+		draw: impl Fn(&T, &mut Commands, &WorldCell) -> Option<Entity>,
+	) {
+		sprites.clear(commands);
+		let cells: Vec<_> = self.data.keys().copied().collect();
+		self.redraw(commands, sprites, cells, draw);
+	}
+
+	/// Draws the given cells with `draw`, despawning the sprites drawn for them before, e.g. to
+	/// catch up on the cells a [crate::plugin::LayerChanged] event lists.
+	pub fn redraw(
+		&self,
+		commands: &mut Commands,
+		sprites: &mut CellSprites,
+		cells: impl IntoIterator<Item = GridPosition>,
+		draw: impl Fn(&T, &mut Commands, &WorldCell) -> Option<Entity>,
+	) {
+		for position in cells {
+			if let Some(entity) = sprites.sprites.remove(&position) {
+				commands.entity(entity).despawn();
+			}
+			let world_cell = WorldCell { position: position.into(), cell_size: self.scale };
+			if let Some(entity) = draw(&self.get_grid(position), commands, &world_cell) {
+				sprites.sprites.insert(position, entity);
+			}
+		}
+	}
+}
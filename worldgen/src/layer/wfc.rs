@@ -0,0 +1,436 @@
+use crate::layer::base::{streams, NoiseGenerator};
+use crate::layer::{GridPosition, Layer, LayerValue, Neighborhood, WorldCell, WorldPosition};
+use rand::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Which values may sit orthogonally next to each other, along with how often each is picked.
+///
+/// Rules are symmetric and hold at most 64 values.
+#[derive(Clone, Debug)]
+pub struct AdjacencyRules<T: LayerValue> {
+	values: Vec<T>,
+	weights: Vec<u32>,
+	allowed: Vec<u64>,
+}
+
+impl<T: LayerValue> AdjacencyRules<T> {
+	/// Creates rules over the given values and their weights, where no two values may be
+	/// adjacent yet.
+	pub fn new(values: impl IntoIterator<Item = (T, u32)>) -> Self {
+		let (values, weights): (Vec<_>, Vec<_>) = values.into_iter().unzip();
+		assert!(values.len() <= 64, "adjacency rules hold at most 64 values");
+		let allowed = vec![0; values.len()];
+		Self { values, weights, allowed }
+	}
+
+	/// Allows `a` and `b` next to each other, where `allow(a, a)` lets `a` neighbor itself.
+	pub fn allow(mut self, a: T, b: T) -> Self {
+		let a = self.index(a).expect("allowed value is not part of the rules");
+		let b = self.index(b).expect("allowed value is not part of the rules");
+		self.allowed[a] |= 1 << b;
+		self.allowed[b] |= 1 << a;
+		self
+	}
+
+	/// Whether `a` may sit next to `b`.
+	pub fn allows(&self, a: T, b: T) -> bool {
+		match (self.index(a), self.index(b)) {
+			(Some(a), Some(b)) => self.allowed[a] & 1 << b != 0,
+			_ => false,
+		}
+	}
+
+	fn index(&self, value: T) -> Option<usize> {
+		self.values.iter().position(|v| *v == value)
+	}
+
+	/// The values that may sit next to at least one value of the domain.
+	fn support(&self, domain: u64) -> u64 {
+		bits(domain).fold(0, |support, index| support | self.allowed[index])
+	}
+}
+
+/// The indices of the values in a domain.
+fn bits(domain: u64) -> impl Iterator<Item = usize> {
+	(0..64).filter(move |index| domain & 1 << index != 0)
+}
+
+/// Why the [WfcGenerator] couldn't fill a region.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WfcError {
+	/// No value fits the cell, whatever values are picked elsewhere.
+	Contradiction(WorldPosition),
+	/// Backtracking gave up after the given number of attempts.
+	BacktrackLimit(usize),
+}
+
+impl fmt::Display for WfcError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			WfcError::Contradiction(position) => {
+				write!(f, "no value fits the cell at ({}, {})", position.x, position.y)
+			}
+			WfcError::BacktrackLimit(limit) => {
+				write!(f, "gave up after backtracking {limit} times")
+			}
+		}
+	}
+}
+
+impl std::error::Error for WfcError {}
+
+/// Fills layers with Wave Function Collapse, as an alternative to a [crate::layer::LayerFactory]
+/// for content that needs strict adjacency rather than noise.
+pub struct WfcGenerator<T: LayerValue> {
+	noise_gen: NoiseGenerator,
+	rules: AdjacencyRules<T>,
+	max_backtracks: usize,
+}
+
+impl<T: LayerValue> WfcGenerator<T> {
+	pub fn new(noise_gen: NoiseGenerator, rules: AdjacencyRules<T>) -> Self {
+		Self { noise_gen, rules, max_backtracks: 1000 }
+	}
+
+	/// Sets how often the generator may backtrack on a contradiction before giving up.
+	pub fn with_max_backtracks(mut self, max_backtracks: usize) -> Self {
+		self.max_backtracks = max_backtracks;
+		self
+	}
+
+	/// Fills the given cells of the layer so that orthogonal neighbors among them follow the
+	/// rules.
+	///
+	/// Cells already set in the layer keep their value and constrain their neighbors, and
+	/// `constraint` may rule out values per cell, e.g. based on coarser layers. Cells outside
+	/// the region are neither changed nor considered.
+	pub fn fill(
+		&self,
+		layer: &mut Layer<T>,
+		region: impl IntoIterator<Item = WorldPosition>,
+		constraint: impl Fn(WorldCell, T) -> bool,
+	) -> Result<(), WfcError> {
+		let mut cells: Vec<GridPosition> = Vec::new();
+		let mut indices = HashMap::new();
+		for position in region {
+			let grid_position = layer.get_grid_position(position);
+			if layer.contains(position) && !indices.contains_key(&grid_position) {
+				indices.insert(grid_position, cells.len());
+				cells.push(grid_position);
+			}
+		}
+		let Some(first) = cells.first() else {
+			return Ok(());
+		};
+		if self.rules.values.is_empty() {
+			return Err(WfcError::Contradiction((*first).into()));
+		}
+
+		let neighbors = cells
+			.iter()
+			.map(|cell| {
+				Neighborhood::VonNeumann
+					.offsets()
+					.into_iter()
+					.filter_map(|(x, y)| {
+						indices.get(&GridPosition::new(cell.x + x, cell.y + y, cell.scale)).copied()
+					})
+					.collect()
+			})
+			.collect();
+
+		// Pre-filled cells are fixed, every other cell starts out with the values its
+		// constraint allows.
+		let all = u64::MAX >> (64 - self.rules.values.len());
+		let mut domains = Vec::with_capacity(cells.len());
+		for cell in &cells {
+			let position = WorldPosition::from(*cell);
+			let value = layer.get_grid(*cell);
+			let domain = if value != T::default() {
+				self.rules.index(value).map_or(0, |index| 1 << index)
+			} else {
+				let world_cell = WorldCell { position, cell_size: cell.scale };
+				bits(all)
+					.filter(|index| constraint(world_cell, self.rules.values[*index]))
+					.fold(0, |domain, index| domain | 1 << index)
+			};
+			if domain == 0 {
+				return Err(WfcError::Contradiction(position));
+			}
+			domains.push(domain);
+		}
+
+		let mut solver = Solver { rules: &self.rules, domains, neighbors, trail: Vec::new() };
+		let contradiction = |cell: usize| WfcError::Contradiction(cells[cell].into());
+		solver.propagate((0..cells.len()).collect()).map_err(contradiction)?;
+
+		let seed = self.noise_gen.get_noise_value(&(*first).into(), streams::WFC);
+		let mut rng = StdRng::seed_from_u64(seed as u64);
+		let mut decisions: Vec<(usize, usize, usize)> = Vec::new();
+		let mut backtracks = 0;
+
+		// Collapse the undecided cell with the fewest options, the earliest on ties.
+		while let Some(cell) = (0..cells.len())
+			.filter(|cell| solver.domains[*cell].count_ones() > 1)
+			.min_by_key(|cell| solver.domains[*cell].count_ones())
+		{
+			let value = self.pick(solver.domains[cell], &mut rng);
+			decisions.push((solver.trail.len(), cell, value));
+			solver.restrict(cell, 1 << value);
+			let mut result = solver.propagate(vec![cell]);
+
+			// Undo the latest decision and rule out its value until the domains are consistent.
+			while let Err(failed) = result {
+				let Some((trail_len, cell, value)) = decisions.pop() else {
+					return Err(contradiction(failed));
+				};
+				backtracks += 1;
+				if backtracks > self.max_backtracks {
+					return Err(WfcError::BacktrackLimit(self.max_backtracks));
+				}
+				solver.undo(trail_len);
+				solver.restrict(cell, solver.domains[cell] & !(1 << value));
+				result = solver.propagate(vec![cell]);
+			}
+		}
+
+		for (cell, domain) in cells.iter().zip(solver.domains) {
+			layer.set_grid(*cell, self.rules.values[domain.trailing_zeros() as usize]);
+		}
+		Ok(())
+	}
+
+	/// Picks a value of the domain according to the weights.
+	fn pick(&self, domain: u64, rng: &mut StdRng) -> usize {
+		let total: u64 = bits(domain).map(|index| self.rules.weights[index] as u64).sum();
+		if total == 0 {
+			return domain.trailing_zeros() as usize;
+		}
+
+		let mut roll = rng.gen_range(0..total);
+		for index in bits(domain) {
+			let weight = self.rules.weights[index] as u64;
+			if roll < weight {
+				return index;
+			}
+			roll -= weight;
+		}
+		unreachable!("the roll is below the total weight")
+	}
+}
+
+/// The domains of a region being collapsed, with a trail of changes to undo on backtracking.
+struct Solver<'a, T: LayerValue> {
+	rules: &'a AdjacencyRules<T>,
+	domains: Vec<u64>,
+	neighbors: Vec<Vec<usize>>,
+	trail: Vec<(usize, u64)>,
+}
+
+impl<T: LayerValue> Solver<'_, T> {
+	fn restrict(&mut self, cell: usize, domain: u64) {
+		self.trail.push((cell, self.domains[cell]));
+		self.domains[cell] = domain;
+	}
+
+	/// Narrows the neighbors of the changed cells until every value has a fitting neighbor,
+	/// failing with the first cell left without values.
+	fn propagate(&mut self, mut queue: Vec<usize>) -> Result<(), usize> {
+		if let Some(cell) = queue.iter().copied().find(|cell| self.domains[*cell] == 0) {
+			return Err(cell);
+		}
+
+		while let Some(cell) = queue.pop() {
+			let support = self.rules.support(self.domains[cell]);
+			for index in 0..self.neighbors[cell].len() {
+				let neighbor = self.neighbors[cell][index];
+				let domain = self.domains[neighbor] & support;
+				if domain == self.domains[neighbor] {
+					continue;
+				}
+				self.restrict(neighbor, domain);
+				if domain == 0 {
+					return Err(neighbor);
+				}
+				queue.push(neighbor);
+			}
+		}
+		Ok(())
+	}
+
+	fn undo(&mut self, trail_len: usize) {
+		while self.trail.len() > trail_len {
+			let (cell, domain) = self.trail.pop().expect("the trail is longer than its target");
+			self.domains[cell] = domain;
+		}
+	}
+}
+
+/// Generates a whole layer with Wave Function Collapse, where `constraint` may rule out values
+/// per cell.
+pub fn generate_wfc_layer<T: LayerValue>(
+	noise_gen: &NoiseGenerator,
+	scale: u32,
+	world_size: u32,
+	rules: AdjacencyRules<T>,
+	constraint: impl Fn(WorldCell, T) -> bool,
+) -> Result<Layer<T>, WfcError> {
+	let mut layer = Layer::new_base32(scale, world_size);
+	let region: Vec<_> = layer.positions().collect();
+	WfcGenerator::new(noise_gen.clone(), rules).fill(&mut layer, region, constraint)?;
+	Ok(layer)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Clone, Copy, Default, PartialEq, Debug)]
+	enum Tile {
+		#[default]
+		Empty,
+		Red,
+		Green,
+		Blue,
+		Yellow,
+		Purple,
+	}
+
+	impl LayerValue for Tile {}
+
+	/// Neighbors must differ, i.e. a three-coloring of the grid.
+	fn coloring() -> AdjacencyRules<Tile> {
+		AdjacencyRules::new([(Tile::Red, 1), (Tile::Green, 1), (Tile::Blue, 1)])
+			.allow(Tile::Red, Tile::Green)
+			.allow(Tile::Green, Tile::Blue)
+			.allow(Tile::Blue, Tile::Red)
+	}
+
+	fn checkerboard() -> AdjacencyRules<Tile> {
+		AdjacencyRules::new([(Tile::Red, 1), (Tile::Green, 1)]).allow(Tile::Red, Tile::Green)
+	}
+
+	fn assert_follows(layer: &Layer<Tile>, rules: &AdjacencyRules<Tile>) {
+		for position in layer.positions() {
+			let cell = WorldCell { position, cell_size: layer.scale() };
+			for neighbor in layer.neighbors(&cell, Neighborhood::VonNeumann) {
+				assert!(rules.allows(layer.get(position), neighbor), "broken rule at {position:?}");
+			}
+		}
+	}
+
+	#[test]
+	fn filled_layers_follow_the_rules() {
+		for seed in 0..32 {
+			let layer =
+				generate_wfc_layer(&NoiseGenerator::new(seed), 0, 3, coloring(), |_, _| true)
+					.unwrap();
+			assert_follows(&layer, &coloring());
+		}
+	}
+
+	#[test]
+	fn generation_is_deterministic() {
+		let noise_gen = NoiseGenerator::new(7);
+		let a = generate_wfc_layer(&noise_gen, 0, 3, coloring(), |_, _| true).unwrap();
+		let b = generate_wfc_layer(&noise_gen, 0, 3, coloring(), |_, _| true).unwrap();
+
+		assert!(a.positions().all(|position| a.get(position) == b.get(position)));
+	}
+
+	#[test]
+	fn pre_filled_cells_are_kept() {
+		let mut layer = Layer::new_base32(0, 3);
+		layer.set(WorldPosition::new(0, 0), Tile::Green);
+
+		let region: Vec<_> = layer.positions().collect();
+		WfcGenerator::new(NoiseGenerator::new(7), checkerboard())
+			.fill(&mut layer, region, |_, _| true)
+			.unwrap();
+
+		// A single pre-filled cell decides the whole checkerboard.
+		for position in layer.positions() {
+			let expected = if (position.x + position.y) % 2 == 0 { Tile::Green } else { Tile::Red };
+			assert_eq!(layer.get(position), expected);
+		}
+	}
+
+	#[test]
+	fn constraints_rule_out_values() {
+		let layer = generate_wfc_layer(&NoiseGenerator::new(7), 0, 3, coloring(), |cell, tile| {
+			cell.position.x >= 0 || tile != Tile::Blue
+		})
+		.unwrap();
+
+		assert_follows(&layer, &coloring());
+		for position in layer.positions().filter(|position| position.x < 0) {
+			assert_ne!(layer.get(position), Tile::Blue);
+		}
+	}
+
+	#[test]
+	fn fill_leaves_cells_outside_the_region_alone() {
+		let mut layer = Layer::new_base32(0, 3);
+		let region: Vec<_> = layer.positions().filter(|position| position.y >= 0).collect();
+		WfcGenerator::new(NoiseGenerator::new(7), coloring())
+			.fill(&mut layer, region, |_, _| true)
+			.unwrap();
+
+		for position in layer.positions() {
+			assert_eq!(layer.get(position) == Tile::Empty, position.y < 0);
+		}
+	}
+
+	#[test]
+	fn unsatisfiable_regions_fail() {
+		let mut layer = Layer::new_base32(0, 3);
+		layer.set(WorldPosition::new(0, 0), Tile::Green);
+		layer.set(WorldPosition::new(1, 0), Tile::Green);
+
+		let region: Vec<_> = layer.positions().collect();
+		let result = WfcGenerator::new(NoiseGenerator::new(7), checkerboard()).fill(
+			&mut layer,
+			region,
+			|_, _| true,
+		);
+
+		assert!(matches!(result, Err(WfcError::Contradiction(_))));
+	}
+
+	#[test]
+	fn backtracking_resolves_dead_ends() {
+		let rules = AdjacencyRules::new([
+			(Tile::Red, 1),
+			(Tile::Green, 1),
+			(Tile::Blue, 1),
+			(Tile::Yellow, 1),
+			(Tile::Purple, 1),
+		])
+		.allow(Tile::Red, Tile::Green)
+		.allow(Tile::Red, Tile::Blue)
+		.allow(Tile::Red, Tile::Yellow)
+		.allow(Tile::Green, Tile::Blue)
+		.allow(Tile::Green, Tile::Purple)
+		.allow(Tile::Blue, Tile::Yellow)
+		.allow(Tile::Purple, Tile::Purple);
+
+		let mut dead_ends = 0;
+		for seed in 0..64 {
+			let generator = WfcGenerator::new(NoiseGenerator::new(seed), rules.clone());
+			let mut layer = Layer::new_base32(0, 3);
+			let region: Vec<_> = layer.positions().collect();
+			generator.fill(&mut layer, region.clone(), |_, _| true).unwrap();
+			assert_follows(&layer, &rules);
+
+			let mut layer = Layer::new_base32(0, 3);
+			let result = generator.with_max_backtracks(0).fill(&mut layer, region, |_, _| true);
+			if result == Err(WfcError::BacktrackLimit(0)) {
+				dead_ends += 1;
+			}
+		}
+
+		// Some seeds run into dead ends that only backtracking gets out of.
+		assert!(dead_ends > 0);
+	}
+}
@@ -0,0 +1,16 @@
+pub mod change;
+pub mod disaster;
+pub mod dungeon;
+pub mod edit;
+pub mod history;
+pub mod invariant;
+pub mod layer;
+pub mod names;
+#[cfg(feature = "bevy")]
+pub mod plugin;
+pub mod portal;
+pub mod season;
+pub mod simulation;
+pub mod stamp;
+pub mod stats;
+pub mod world;
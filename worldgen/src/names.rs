@@ -0,0 +1,346 @@
+use crate::layer::base::{streams, NoiseGenerator};
+use crate::layer::layers::biome::Biome;
+use crate::layer::layers::urban::Urban;
+use crate::layer::layers::water::{Landmass, WaterType};
+use crate::layer::region::{RegionId, RegionMap};
+use crate::layer::{Layer, WorldPosition};
+#[cfg(feature = "bevy")]
+use bevy::prelude::*;
+use rand::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// The sounds names in one biome are made of, e.g. the harsh consonants of the tundra.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhoneticStyle {
+	pub onsets: &'static [&'static str],
+	pub vowels: &'static [&'static str],
+	pub codas: &'static [&'static str],
+	/// The least and most syllables in a name.
+	pub syllables: (usize, usize),
+}
+
+impl PhoneticStyle {
+	/// The phonetic style of places in the given biome.
+	pub fn of(biome: Biome) -> Self {
+		match biome {
+			Biome::Desert => PhoneticStyle {
+				onsets: &["k", "q", "z", "sh", "r", "m", "d", "kh", "s"],
+				vowels: &["a", "aa", "i", "u", "e"],
+				codas: &["", "", "r", "d", "n", "sh", "m"],
+				syllables: (2, 3),
+			},
+			Biome::Grassland => PhoneticStyle {
+				onsets: &["b", "l", "m", "st", "w", "h", "br", "d", "c"],
+				vowels: &["a", "e", "o", "i", "ea"],
+				codas: &["", "n", "ll", "rd", "ck", "m", "ford", "ton"],
+				syllables: (2, 2),
+			},
+			Biome::Forest => PhoneticStyle {
+				onsets: &["l", "th", "s", "f", "n", "v", "gl", "r"],
+				vowels: &["a", "e", "i", "ae", "ia", "o"],
+				codas: &["", "", "l", "n", "th", "r", "wen"],
+				syllables: (2, 3),
+			},
+			Biome::Jungle => PhoneticStyle {
+				onsets: &["t", "k", "p", "x", "ch", "tl", "y", "hu"],
+				vowels: &["a", "o", "u", "i"],
+				codas: &["", "", "", "l", "n", "c"],
+				syllables: (3, 4),
+			},
+			Biome::Tundra => PhoneticStyle {
+				onsets: &["sk", "v", "h", "g", "tr", "k", "fj", "b"],
+				vowels: &["a", "e", "o", "i", "ei"],
+				codas: &["", "rd", "k", "n", "ll", "g", "heim"],
+				syllables: (2, 2),
+			},
+			Biome::Snow => PhoneticStyle {
+				onsets: &["kr", "dr", "g", "v", "z", "gr", "k"],
+				vowels: &["o", "u", "a", "y"],
+				codas: &["", "k", "g", "v", "rn", "sk"],
+				syllables: (1, 2),
+			},
+		}
+	}
+
+	/// Strings syllables of the style together into a capitalized name.
+	fn name(&self, rng: &mut StdRng) -> String {
+		let syllables = rng.gen_range(self.syllables.0..=self.syllables.1);
+		let mut name = String::new();
+		for syllable in 0..syllables {
+			name.push_str(self.onsets.choose(rng).expect("styles have onsets"));
+			name.push_str(self.vowels.choose(rng).expect("styles have vowels"));
+			// Codas only close the name, or a syllable now and then, to keep names pronounceable.
+			if syllable + 1 == syllables || rng.gen_bool(0.25) {
+				name.push_str(self.codas.choose(rng).expect("styles have codas"));
+			}
+		}
+
+		let mut chars = name.chars();
+		chars
+			.next()
+			.map(|first| first.to_uppercase().chain(chars).collect())
+			.unwrap_or_default()
+	}
+}
+
+/// What a [PlaceName] names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PlaceKind {
+	Landmass,
+	Ocean,
+	Lake,
+	City,
+	Port,
+}
+
+/// A named place of the world.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlaceName {
+	pub kind: PlaceKind,
+	pub name: String,
+	/// Where the label goes, at the center of the place.
+	pub position: WorldPosition,
+	/// The number of cells of the place, for picking which labels to show.
+	pub cells: usize,
+}
+
+/// The names of the places of a generated world.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "bevy", derive(Resource))]
+pub struct WorldNames {
+	places: Vec<PlaceName>,
+}
+
+impl WorldNames {
+	/// Names the landmasses, oceans, lakes, cities and ports of the world, in the style of the
+	/// biome they lie in.
+	///
+	/// Names are unique and depend only on the seed and the layers.
+	pub fn generate(
+		noise_gen: &NoiseGenerator,
+		water: &Layer<WaterType>,
+		biome: &Layer<Biome>,
+		urban: &Layer<Urban>,
+	) -> Self {
+		let mut names = Namer { noise_gen, used: HashSet::new(), places: Vec::new() };
+
+		// Each landmass takes the most common biome on it, and the water around or within it
+		// takes the style of the largest landmass touching it.
+		let landmasses = water.landmasses();
+		let mut biome_counts: HashMap<RegionId, [usize; Biome::ALL.len()]> = HashMap::new();
+		for position in biome.positions().filter(|position| !water.get(*position).is_water()) {
+			if let Some(landmass) = landmasses.region_at(position) {
+				biome_counts.entry(landmass.id).or_default()[biome.get(position) as usize] += 1;
+			}
+		}
+		let mut landmass_biomes = HashMap::new();
+		for landmass in landmasses.with_key(Landmass::Land) {
+			let counts = biome_counts.get(&landmass.id).copied().unwrap_or_default();
+			let dominant = Biome::ALL
+				.into_iter()
+				.max_by_key(|biome| counts[*biome as usize])
+				.unwrap_or_default();
+			landmass_biomes.insert(landmass.id, dominant);
+			names.name(PlaceKind::Landmass, centroid(landmass.centroid), landmass.cells, dominant);
+		}
+
+		for ocean in landmasses.with_key(Landmass::Ocean) {
+			let shore = landmasses
+				.neighbors(ocean.id)
+				.max_by_key(|landmass| landmass.cells)
+				.and_then(|landmass| landmass_biomes.get(&landmass.id).copied())
+				.unwrap_or(Biome::Grassland);
+			names.name(PlaceKind::Ocean, centroid(ocean.centroid), ocean.cells, shore);
+		}
+
+		for lake in RegionMap::of(water).with_key(WaterType::Lake) {
+			let position = centroid(lake.centroid);
+			let shore = landmasses
+				.region_at(position)
+				.and_then(|landmass| landmass_biomes.get(&landmass.id).copied())
+				.unwrap_or(Biome::Grassland);
+			names.name(PlaceKind::Lake, position, lake.cells, shore);
+		}
+
+		let mut names = Self { places: names.places };
+		names.name_settlements(noise_gen, biome, urban);
+		names
+	}
+
+	/// Brings the names of cities and ports up to date with the urban layer, e.g. as the
+	/// simulation builds and ruins them: settlements that are gone lose their names, new ones
+	/// are named, and the others keep theirs.
+	///
+	/// Returns whether any name was added or removed.
+	pub fn name_settlements(
+		&mut self,
+		noise_gen: &NoiseGenerator,
+		biome: &Layer<Biome>,
+		urban: &Layer<Urban>,
+	) -> bool {
+		let before = self.places.len();
+		let settlement = |position: WorldPosition| match urban.get(position) {
+			Urban::City => Some(PlaceKind::City),
+			Urban::Port => Some(PlaceKind::Port),
+			_ => None,
+		};
+		self.places.retain(|place| {
+			!matches!(place.kind, PlaceKind::City | PlaceKind::Port)
+				|| settlement(place.position) == Some(place.kind)
+		});
+		let kept = self.places.len();
+		let named: HashSet<_> = self
+			.places
+			.iter()
+			.filter(|place| matches!(place.kind, PlaceKind::City | PlaceKind::Port))
+			.map(|place| place.position)
+			.collect();
+
+		let mut names = Namer {
+			noise_gen,
+			used: self.places.iter().map(|place| place.name.clone()).collect(),
+			places: std::mem::take(&mut self.places),
+		};
+		for position in urban.positions() {
+			let Some(kind) = settlement(position) else {
+				continue;
+			};
+			let half = urban.scale() / 2;
+			let center = WorldPosition::new(position.x + half, position.y + half);
+			if !named.contains(&center) {
+				names.name(kind, center, 1, biome.get(position));
+			}
+		}
+		self.places = names.places;
+		kept != before || self.places.len() != kept
+	}
+
+	/// Iterates every named place.
+	pub fn iter(&self) -> impl Iterator<Item = &PlaceName> {
+		self.places.iter()
+	}
+
+	/// Iterates the named places of the given kind.
+	pub fn of_kind(&self, kind: PlaceKind) -> impl Iterator<Item = &PlaceName> {
+		self.places.iter().filter(move |place| place.kind == kind)
+	}
+}
+
+/// Rounds a region centroid to the position its label is anchored at.
+fn centroid((x, y): (f64, f64)) -> WorldPosition {
+	WorldPosition::new(x.round() as i64, y.round() as i64)
+}
+
+/// Hands out unique names as places are visited.
+struct Namer<'a> {
+	noise_gen: &'a NoiseGenerator,
+	used: HashSet<String>,
+	places: Vec<PlaceName>,
+}
+
+impl Namer<'_> {
+	fn name(&mut self, kind: PlaceKind, position: WorldPosition, cells: usize, biome: Biome) {
+		let seed = self.noise_gen.get_noise_value(&position, streams::NAMES);
+		let mut rng = StdRng::seed_from_u64(seed as u64 ^ ((kind as u64) << 32));
+		let style = PhoneticStyle::of(biome);
+
+		// Re-roll on collisions, falling back to numbering if a style runs out of names.
+		let mut base = style.name(&mut rng);
+		for _ in 0..16 {
+			if !self.used.contains(&decorate(kind, &base)) {
+				break;
+			}
+			base = style.name(&mut rng);
+		}
+		let mut name = decorate(kind, &base);
+		let mut number = 2;
+		while self.used.contains(&name) {
+			name = decorate(kind, &format!("{base} {number}"));
+			number += 1;
+		}
+
+		self.used.insert(name.clone());
+		self.places.push(PlaceName { kind, name, position, cells });
+	}
+}
+
+/// Dresses a bare name up for the kind of place, e.g. "Lake Brenton".
+fn decorate(kind: PlaceKind, name: &str) -> String {
+	match kind {
+		PlaceKind::Ocean => format!("{name} Sea"),
+		PlaceKind::Lake => format!("Lake {name}"),
+		PlaceKind::Port => format!("Port {name}"),
+		PlaceKind::Landmass | PlaceKind::City => name.to_string(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::world::{WorldGenConfig, WorldLayers};
+
+	#[test]
+	fn names_are_deterministic_and_unique() {
+		let config = WorldGenConfig { seed: 3, ..WorldGenConfig::default() };
+		let a = WorldLayers::generate(&config).names;
+		let b = WorldLayers::generate(&config).names;
+
+		let names: Vec<_> = a.iter().map(|place| &place.name).collect();
+		assert_eq!(names, b.iter().map(|place| &place.name).collect::<Vec<_>>());
+		assert_eq!(names.iter().collect::<HashSet<_>>().len(), names.len());
+	}
+
+	#[test]
+	fn cities_and_ports_are_named() {
+		let world = WorldLayers::generate(&WorldGenConfig::default());
+
+		for (kind, urban) in [(PlaceKind::City, Urban::City), (PlaceKind::Port, Urban::Port)] {
+			let settlements =
+				world.urban.positions().filter(|position| world.urban.get(*position) == urban);
+			assert_eq!(settlements.count(), world.names.of_kind(kind).count());
+		}
+		assert!(world.names.of_kind(PlaceKind::Port).all(|port| port.name.starts_with("Port ")));
+	}
+
+	#[test]
+	fn settlements_are_renamed_as_they_change() {
+		let mut world = WorldLayers::generate(&WorldGenConfig::default());
+		let noise_gen = NoiseGenerator::new(WorldGenConfig::default().seed);
+		let mut cities = world.names.of_kind(PlaceKind::City);
+		let (ruined, kept) = (cities.next().unwrap().clone(), cities.next().unwrap().clone());
+		let founded = world
+			.urban
+			.positions()
+			.find(|position| {
+				world.urban.get(*position) == Urban::None && !world.water.get(*position).is_water()
+			})
+			.unwrap();
+		world.urban.set(ruined.position, Urban::Ruin);
+		world.urban.set(founded, Urban::City);
+
+		let mut names = world.names.clone();
+		assert!(names.name_settlements(&noise_gen, &world.biome, &world.urban));
+		assert!(!names.name_settlements(&noise_gen, &world.biome, &world.urban));
+		let cities: Vec<_> = names.of_kind(PlaceKind::City).collect();
+		assert_eq!(cities.len(), world.names.of_kind(PlaceKind::City).count());
+		assert!(cities.iter().all(|city| city.name != ruined.name));
+		assert!(cities.contains(&&kept));
+		let half = world.urban.scale() / 2;
+		let founded = WorldPosition::new(founded.x + half, founded.y + half);
+		assert!(cities.iter().any(|city| city.position == founded));
+	}
+
+	#[test]
+	fn styles_differ_between_biomes() {
+		let mut desert = StdRng::seed_from_u64(0);
+		let mut tundra = StdRng::seed_from_u64(0);
+
+		let desert: HashSet<_> =
+			(0..32).map(|_| PhoneticStyle::of(Biome::Desert).name(&mut desert)).collect();
+		let tundra: HashSet<_> =
+			(0..32).map(|_| PhoneticStyle::of(Biome::Tundra).name(&mut tundra)).collect();
+
+		assert!(desert.is_disjoint(&tundra));
+		assert!(desert.iter().all(|name| name.chars().next().unwrap().is_uppercase()));
+	}
+}
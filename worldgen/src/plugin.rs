@@ -0,0 +1,271 @@
+use crate::change::{ChangeLog, WorldValue};
+use crate::disaster::Disasters;
+use crate::dungeon::Dungeons;
+use crate::history::History;
+use crate::layer::base::NoiseGenerator;
+use crate::layer::layers::biome::Biome;
+use crate::layer::layers::detail::TerrainDetail;
+use crate::layer::layers::flora::Flora;
+use crate::layer::layers::mineral::Mineral;
+use crate::layer::layers::special::Special;
+use crate::layer::layers::terrain::TerrainFeature;
+use crate::layer::layers::territory::TerritoryId;
+use crate::layer::layers::urban::Urban;
+use crate::layer::layers::water::WaterType;
+use crate::layer::{ChunkPosition, GridPosition, Layer, LayerValue};
+use crate::names::WorldNames;
+use crate::portal::{teleport_travelers, PortalNetwork, Teleported};
+use crate::season::{Season, SeasonalLayers};
+use crate::simulation::{advance_simulation, SimulationClock, UrbanSimulation};
+use crate::world::{LayerKind, WorldGenConfig, WorldLayers};
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+/// Sent whenever the [WorldGenPlugin] has (re)generated the world.
+#[derive(Event, Clone, Debug)]
+pub struct WorldGenerated {
+	/// The configuration the world was generated from.
+	pub config: WorldGenConfig,
+}
+
+/// Sent for each layer resource whose cells changed since the last update, e.g. as the
+/// simulation rewrites them, so that whatever mirrors the layer can catch up on those cells
+/// alone.
+#[derive(Event, Clone, Debug)]
+pub struct LayerChanged {
+	pub kind: LayerKind,
+	/// The changed cells, in no particular order.
+	pub cells: Vec<GridPosition>,
+}
+
+impl LayerChanged {
+	/// The chunks of `size` by `size` cells holding the changed cells.
+	pub fn chunks(&self, size: i64) -> HashSet<ChunkPosition> {
+		self.cells.iter().map(|cell| cell.chunk(size)).collect()
+	}
+}
+
+/// Generates the world from the [WorldGenConfig] resource and exposes each enabled layer as a
+/// `Layer<T>` resource, along with the [WorldNames] of its places, its [PortalNetwork] and its
+/// [Dungeons], which are kept up to date as the urban and special layers change.
+///
+/// The world is regenerated whenever the config changes, after which [WorldGenerated] is sent.
+/// Entities with a [crate::portal::PortalTraveler] are teleported as they enter portals.
+///
+/// While the [SimulationClock] runs, the [UrbanSimulation] of the world steps a year per tick,
+/// with [Disasters] striking it, and every change to the layer resources is recorded in the
+/// [ChangeLog] resource, which the [History] resource drains every frame to record what can be
+/// undone, making [History::to_log] the record of the changes to save. Every update that changes
+/// cells of a layer resource ends with a [LayerChanged] event listing them.
+///
+/// The [SeasonalLayers] resource holds the water and terrain detail in the current [Season],
+/// which are recomputed without regenerating the world whenever the season changes.
+pub struct WorldGenPlugin;
+
+impl Plugin for WorldGenPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<WorldGenConfig>()
+			.init_resource::<Season>()
+			.init_resource::<SimulationClock>()
+			.init_resource::<ChangeLog>()
+			.init_resource::<History>()
+			.add_event::<WorldGenerated>()
+			.add_event::<Teleported>()
+			.add_event::<LayerChanged>()
+			.add_systems(
+				PreUpdate,
+				(
+					generate_world.run_if(resource_changed::<WorldGenConfig>),
+					apply_season.run_if(resource_changed::<Season>.or(on_event::<WorldGenerated>)),
+				)
+					.chain(),
+			)
+			.add_systems(Update, (teleport_travelers, advance_simulation))
+			.add_systems(
+				PostUpdate,
+				(
+					record_history,
+					(
+						(
+							send_layer_changes::<WaterType>,
+							send_layer_changes::<TerrainFeature>,
+							send_layer_changes::<Biome>,
+							send_layer_changes::<TerrainDetail>,
+							send_layer_changes::<Flora>,
+							send_layer_changes::<Mineral>,
+							send_layer_changes::<Urban>,
+							send_layer_changes::<Special>,
+							send_layer_changes::<TerritoryId>,
+						),
+						(refresh_season, rename_settlements, refresh_sites),
+					)
+						.chain(),
+				),
+			);
+	}
+}
+
+fn generate_world(
+	mut commands: Commands,
+	config: Res<WorldGenConfig>,
+	mut history: ResMut<History>,
+	mut generated: EventWriter<WorldGenerated>,
+) {
+	let layers = WorldLayers::generate(&config);
+	let noise_gen = NoiseGenerator::new(config.seed);
+	commands.insert_resource(UrbanSimulation::new(&noise_gen));
+	commands.insert_resource(Disasters::new(&noise_gen, &layers.special));
+	// Changes recorded against the previous world no longer apply.
+	commands.insert_resource(ChangeLog::new());
+	history.clear();
+
+	insert_layer(&mut commands, config.is_enabled(LayerKind::Water), layers.water);
+	insert_layer(&mut commands, config.is_enabled(LayerKind::Terrain), layers.terrain);
+	insert_layer(&mut commands, config.is_enabled(LayerKind::Biome), layers.biome);
+	insert_layer(&mut commands, config.is_enabled(LayerKind::Detail), layers.detail);
+	insert_layer(&mut commands, config.is_enabled(LayerKind::Flora), layers.flora);
+	insert_layer(&mut commands, config.is_enabled(LayerKind::Mineral), layers.mineral);
+	insert_layer(&mut commands, config.is_enabled(LayerKind::Urban), layers.urban);
+	insert_layer(&mut commands, config.is_enabled(LayerKind::Special), layers.special);
+	insert_layer(&mut commands, config.is_enabled(LayerKind::Territory), layers.territory);
+	commands.insert_resource(layers.names);
+	commands.insert_resource(layers.portals);
+	commands.insert_resource(layers.dungeons);
+
+	generated.send(WorldGenerated { config: config.clone() });
+}
+
+/// Computes the [SeasonalLayers] of the current world, unless a layer they depend on is
+/// disabled.
+fn apply_season(
+	mut commands: Commands,
+	config: Res<WorldGenConfig>,
+	season: Res<Season>,
+	water: Option<Res<Layer<WaterType>>>,
+	terrain: Option<Res<Layer<TerrainFeature>>>,
+	biome: Option<Res<Layer<Biome>>>,
+	detail: Option<Res<Layer<TerrainDetail>>>,
+) {
+	let (Some(water), Some(terrain), Some(biome), Some(detail)) = (water, terrain, biome, detail)
+	else {
+		commands.remove_resource::<SeasonalLayers>();
+		return;
+	};
+	let noise_gen = NoiseGenerator::new(config.seed);
+	commands.insert_resource(SeasonalLayers::new(
+		&noise_gen, *season, &water, &terrain, &biome, &detail,
+	));
+}
+
+/// Brings the [SeasonalLayers] up to date with the changed layers they are computed from.
+fn refresh_season(
+	mut changed: EventReader<LayerChanged>,
+	config: Res<WorldGenConfig>,
+	seasonal: Option<ResMut<SeasonalLayers>>,
+	water: Option<Res<Layer<WaterType>>>,
+	terrain: Option<Res<Layer<TerrainFeature>>>,
+	biome: Option<Res<Layer<Biome>>>,
+	detail: Option<Res<Layer<TerrainDetail>>>,
+) {
+	let (Some(mut seasonal), Some(water), Some(terrain), Some(biome), Some(detail)) =
+		(seasonal, water, terrain, biome, detail)
+	else {
+		changed.clear();
+		return;
+	};
+	let noise_gen = NoiseGenerator::new(config.seed);
+	for event in changed.read() {
+		match event.kind {
+			// Water spreads to its neighbors with the season, so start over.
+			LayerKind::Water | LayerKind::Terrain | LayerKind::Biome => {
+				let season = seasonal.season;
+				*seasonal =
+					SeasonalLayers::new(&noise_gen, season, &water, &terrain, &biome, &detail);
+			}
+			// Changed detail only changes its own cells, which whoever draws them catches up on
+			// through the same event rather than by starting over.
+			LayerKind::Detail => seasonal.bypass_change_detection().refresh_detail(
+				&noise_gen,
+				&water,
+				&terrain,
+				&biome,
+				&detail,
+				event.cells.iter().map(|cell| (*cell).into()),
+			),
+			_ => {}
+		}
+	}
+}
+
+/// Brings the names of cities and ports up to date with the changed urban layer.
+fn rename_settlements(
+	mut changed: EventReader<LayerChanged>,
+	config: Res<WorldGenConfig>,
+	names: Option<ResMut<WorldNames>>,
+	biome: Option<Res<Layer<Biome>>>,
+	urban: Option<Res<Layer<Urban>>>,
+) {
+	if !changed.read().any(|event| event.kind == LayerKind::Urban) {
+		return;
+	}
+	let (Some(mut names), Some(biome), Some(urban)) = (names, biome, urban) else {
+		return;
+	};
+	// Most changes leave the cities and ports alone, and their labels with them.
+	let noise_gen = NoiseGenerator::new(config.seed);
+	if names.bypass_change_detection().name_settlements(&noise_gen, &biome, &urban) {
+		names.set_changed();
+	}
+}
+
+/// Rebuilds the [PortalNetwork], [Dungeons] and [Disasters] from the changed special layer, so
+/// that painted sites work like generated ones and erased sites stop working.
+fn refresh_sites(
+	mut commands: Commands,
+	mut changed: EventReader<LayerChanged>,
+	config: Res<WorldGenConfig>,
+	special: Option<Res<Layer<Special>>>,
+) {
+	if !changed.read().any(|event| event.kind == LayerKind::Special) {
+		return;
+	}
+	let Some(special) = special else {
+		return;
+	};
+	let noise_gen = NoiseGenerator::new(config.seed);
+	commands.insert_resource(PortalNetwork::link(&noise_gen, &special));
+	commands.insert_resource(Dungeons::new(&noise_gen, &special));
+	commands.insert_resource(Disasters::new(&noise_gen, &special));
+}
+
+/// Records the changes made during the update in the [History].
+fn record_history(mut log: ResMut<ChangeLog>, mut history: ResMut<History>) {
+	if log.is_changed() {
+		history.record(&mut log);
+	}
+}
+
+/// Sends a [LayerChanged] event for the layer if any of its cells changed.
+fn send_layer_changes<T: WorldValue>(
+	layer: Option<ResMut<Layer<T>>>,
+	mut changed: EventWriter<LayerChanged>,
+) {
+	let Some(mut layer) = layer.filter(|layer| layer.is_dirty()) else {
+		return;
+	};
+	// Taking the changes is bookkeeping rather than a change to the layer.
+	let cells = layer.bypass_change_detection().take_changes();
+	changed.send(LayerChanged { kind: T::KIND, cells });
+}
+
+/// Exposes the layer as a resource if it is enabled, removing any stale one otherwise.
+///
+/// The cells of the resource that change from then on are sent as [LayerChanged] events.
+fn insert_layer<T: LayerValue>(commands: &mut Commands, enabled: bool, mut layer: Layer<T>) {
+	if enabled {
+		layer.track_changes();
+		commands.insert_resource(layer);
+	} else {
+		commands.remove_resource::<Layer<T>>();
+	}
+}
@@ -0,0 +1,300 @@
+use crate::layer::base::{streams, NoiseGenerator};
+use crate::layer::layers::special::Special;
+use crate::layer::{GridPosition, Layer, WorldCell, WorldPosition};
+#[cfg(feature = "bevy")]
+use bevy::prelude::*;
+use rand::prelude::*;
+use std::collections::HashMap;
+
+/// A portal of the world, leading to the next portal of its network.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Portal {
+	/// The cell of the special layer holding the portal.
+	pub position: WorldPosition,
+	/// The portal this one leads to, or `None` for a portal without a partner.
+	pub destination: Option<WorldPosition>,
+	/// The network the portal belongs to, numbered from 0.
+	pub network: usize,
+}
+
+/// The portals of a generated world, linked in pairs or, with an odd number of portals, with
+/// one network of three leading around in a ring.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "bevy", derive(Resource))]
+pub struct PortalNetwork {
+	portals: Vec<Portal>,
+	indices: HashMap<GridPosition, usize>,
+	cell_size: i64,
+}
+
+impl PortalNetwork {
+	/// Links the `Special::Portal` cells of the layer, shuffled by the seed so partners are
+	/// usually far apart.
+	pub fn link(noise_gen: &NoiseGenerator, special: &Layer<Special>) -> Self {
+		let mut positions: Vec<WorldPosition> = special
+			.positions()
+			.filter(|position| special.get(*position) == Special::Portal)
+			.collect();
+		let seed = noise_gen.get_noise_value(&WorldPosition::new(0, 0), streams::PORTALS);
+		positions.shuffle(&mut StdRng::seed_from_u64(seed as u64));
+
+		// Pair the portals up, leaving the last three to a ring if there is an odd number.
+		let mut networks: Vec<&[WorldPosition]> = positions.chunks(2).collect();
+		if positions.len() > 2 && positions.len() % 2 == 1 {
+			networks.pop();
+			networks.pop();
+			networks.push(&positions[positions.len() - 3..]);
+		}
+
+		let mut portals = Vec::with_capacity(positions.len());
+		for (network, members) in networks.into_iter().enumerate() {
+			for (index, position) in members.iter().enumerate() {
+				let destination = members[(index + 1) % members.len()];
+				portals.push(Portal {
+					position: *position,
+					destination: (destination != *position).then_some(destination),
+					network,
+				});
+			}
+		}
+
+		let indices = portals
+			.iter()
+			.enumerate()
+			.map(|(index, portal)| (special.get_grid_position(portal.position), index))
+			.collect();
+		Self { portals, indices, cell_size: special.scale() }
+	}
+
+	/// Gets the portal whose cell covers the given position.
+	pub fn get(&self, position: WorldPosition) -> Option<&Portal> {
+		let grid_position = GridPosition::new(
+			position.x.div_euclid(self.cell_size),
+			position.y.div_euclid(self.cell_size),
+			self.cell_size,
+		);
+		self.indices.get(&grid_position).map(|index| &self.portals[*index])
+	}
+
+	/// Gets the cell a traveler entering the portal at the given position arrives in.
+	pub fn partner(&self, position: WorldPosition) -> Option<WorldCell> {
+		self.get(position)?
+			.destination
+			.map(|destination| WorldCell { position: destination, cell_size: self.cell_size })
+	}
+
+	/// Iterates every portal, grouped by network.
+	pub fn iter(&self) -> impl Iterator<Item = &Portal> {
+		self.portals.iter()
+	}
+
+	pub fn len(&self) -> usize {
+		self.portals.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.portals.is_empty()
+	}
+
+	/// The cell size of the portal cells.
+	pub fn cell_size(&self) -> i64 {
+		self.cell_size
+	}
+}
+
+#[cfg(feature = "bevy")]
+impl PortalNetwork {
+	/// Draws a line from every portal to its destination, returning the spawned sprites.
+	pub fn render_links(&self, commands: &mut Commands, color: Color) -> Vec<Entity> {
+		let width = (self.cell_size as f32 / 2.0).max(1.0);
+		let center = |position: WorldPosition| {
+			WorldCell { position, cell_size: self.cell_size }
+				.transform()
+				.translation
+				.truncate()
+		};
+
+		let mut entities = Vec::new();
+		for portal in &self.portals {
+			let Some(destination) = portal.destination else {
+				continue;
+			};
+			// Partners lead to each other, so draw their shared link only once.
+			let back = self.get(destination).and_then(|partner| partner.destination);
+			if back == Some(portal.position)
+				&& (destination.x, destination.y) < (portal.position.x, portal.position.y)
+			{
+				continue;
+			}
+
+			let (from, to) = (center(portal.position), center(destination));
+			let delta = to - from;
+			entities.push(
+				commands
+					.spawn((
+						Sprite {
+							color,
+							custom_size: Some(Vec2::new(delta.length(), width)),
+							..default()
+						},
+						Transform::from_translation(((from + to) / 2.0).extend(2.0))
+							.with_rotation(Quat::from_rotation_z(delta.to_angle())),
+					))
+					.id(),
+			);
+		}
+		entities
+	}
+}
+
+/// Marks entities that are teleported when they move into a portal cell.
+#[cfg(feature = "bevy")]
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct PortalTraveler {
+	/// The portal cell the traveler stands in, which it has to leave before teleporting again.
+	portal: Option<WorldPosition>,
+}
+
+/// Sent whenever a [PortalTraveler] is teleported.
+#[cfg(feature = "bevy")]
+#[derive(Event, Clone, Copy, Debug)]
+pub struct Teleported {
+	pub entity: Entity,
+	/// The portal the traveler entered.
+	pub from: WorldPosition,
+	/// The portal the traveler arrived at.
+	pub to: WorldPosition,
+}
+
+/// Moves every [PortalTraveler] entering a portal cell to the center of the partner portal,
+/// keeping its depth.
+#[cfg(feature = "bevy")]
+pub fn teleport_travelers(
+	portals: Option<Res<PortalNetwork>>,
+	mut travelers: Query<(Entity, &mut Transform, &mut PortalTraveler)>,
+	mut teleported: EventWriter<Teleported>,
+) {
+	let Some(portals) = portals else {
+		return;
+	};
+
+	for (entity, mut transform, mut traveler) in &mut travelers {
+		let translation = transform.translation;
+		let position =
+			WorldPosition::new(translation.x.floor() as i64, translation.y.floor() as i64);
+		let portal = portals.get(position).map(|portal| portal.position);
+		if portal == traveler.portal {
+			continue;
+		}
+
+		traveler.portal = portal;
+		let (Some(from), Some(destination)) = (portal, portals.partner(position)) else {
+			continue;
+		};
+		let center = destination.transform().translation;
+		transform.translation = center.truncate().extend(translation.z);
+		// Arriving in the destination doesn't count as entering it.
+		traveler.portal = Some(destination.position);
+		teleported.send(Teleported { entity, from, to: destination.position });
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::world::{WorldGenConfig, WorldLayers};
+
+	/// A 16x16 special layer with portals at the given cells.
+	fn portals(cells: &[(i64, i64)]) -> Layer<Special> {
+		let mut layer = Layer::new_base32(0, 4);
+		for (x, y) in cells {
+			layer.set(WorldPosition::new(*x, *y), Special::Portal);
+		}
+		layer
+	}
+
+	#[test]
+	fn portals_link_in_pairs() {
+		let layer = portals(&[(-8, -8), (7, 7), (0, 3), (-4, 5)]);
+		let network = PortalNetwork::link(&NoiseGenerator::new(1), &layer);
+
+		assert_eq!(network.len(), 4);
+		for portal in network.iter() {
+			let partner = network.partner(portal.position).expect("every portal has a partner");
+			assert_ne!(partner.position, portal.position);
+			assert_eq!(network.partner(partner.position).unwrap().position, portal.position);
+			assert_eq!(network.get(partner.position).unwrap().network, portal.network);
+		}
+		let links = |seed| {
+			let network = PortalNetwork::link(&NoiseGenerator::new(seed), &layer);
+			network.iter().copied().collect::<Vec<_>>()
+		};
+		assert_eq!(links(1), links(1));
+	}
+
+	#[test]
+	fn odd_portals_form_a_ring() {
+		let network =
+			PortalNetwork::link(&NoiseGenerator::new(2), &portals(&[(-8, 0), (0, 0), (7, 0)]));
+
+		let start = WorldPosition::new(-8, 0);
+		let mut visited = vec![start];
+		let mut position = network.partner(start).unwrap().position;
+		while position != start {
+			visited.push(position);
+			position = network.partner(position).unwrap().position;
+		}
+		assert_eq!(visited.len(), 3);
+
+		let lonely = PortalNetwork::link(&NoiseGenerator::new(2), &portals(&[(1, 1)]));
+		assert_eq!(lonely.get(WorldPosition::new(1, 1)).unwrap().destination, None);
+		assert!(lonely.partner(WorldPosition::new(1, 1)).is_none());
+	}
+
+	#[test]
+	fn generated_portals_are_linked() {
+		let world = WorldLayers::generate(&WorldGenConfig { seed: 5, ..WorldGenConfig::default() });
+		let portals = world
+			.special
+			.positions()
+			.filter(|position| world.special.get(*position) == Special::Portal)
+			.count();
+
+		assert_eq!(world.portals.len(), portals);
+		for portal in world.portals.iter() {
+			// Any position within the cell finds the portal.
+			let inside = WorldPosition::new(
+				portal.position.x + world.portals.cell_size() - 1,
+				portal.position.y,
+			);
+			assert_eq!(world.portals.get(inside), Some(portal));
+		}
+	}
+
+	#[cfg(feature = "bevy")]
+	#[test]
+	fn travelers_teleport_once_per_entry() {
+		let network = PortalNetwork::link(&NoiseGenerator::new(1), &portals(&[(-8, -8), (4, 4)]));
+		let mut app = App::new();
+		app.insert_resource(network)
+			.add_event::<Teleported>()
+			.add_systems(Update, teleport_travelers);
+		let traveler = app
+			.world_mut()
+			.spawn((Transform::from_xyz(-7.5, -7.5, 3.0), PortalTraveler::default()))
+			.id();
+
+		app.update();
+		let transform = *app.world().get::<Transform>(traveler).unwrap();
+		assert_eq!(transform.translation, Vec3::new(4.5, 4.5, 3.0));
+
+		// Standing in the destination doesn't teleport the traveler back.
+		app.update();
+		assert_eq!(app.world().get::<Transform>(traveler).unwrap().translation.x, 4.5);
+		let events = app.world().resource::<Events<Teleported>>();
+		let sent: Vec<_> = events.get_cursor().read(events).copied().collect();
+		assert_eq!(sent.len(), 1);
+		assert_eq!(sent[0].from, WorldPosition::new(-8, -8));
+		assert_eq!(sent[0].to, WorldPosition::new(4, 4));
+	}
+}
@@ -0,0 +1,352 @@
+use crate::layer::base::{streams, NoiseGenerator};
+use crate::layer::layers::biome::Biome;
+use crate::layer::layers::detail::TerrainDetail;
+#[cfg(feature = "bevy")]
+use crate::layer::layers::flora::Flora;
+use crate::layer::layers::terrain::TerrainFeature;
+use crate::layer::layers::water::WaterType;
+#[cfg(feature = "bevy")]
+use crate::layer::render::LayerRender;
+use crate::layer::{Layer, Neighborhood, WorldCell, WorldPosition};
+use crate::world::WorldLayers;
+#[cfg(feature = "bevy")]
+use bevy::prelude::*;
+
+/// The time of year, where a generated world shows it in summer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bevy", derive(Resource))]
+pub enum Season {
+	Spring,
+	#[default]
+	Summer,
+	Autumn,
+	Winter,
+}
+
+impl Season {
+	pub const ALL: [Season; 4] = [Season::Spring, Season::Summer, Season::Autumn, Season::Winter];
+
+	/// The season after this one.
+	pub fn next(&self) -> Self {
+		match self {
+			Season::Spring => Season::Summer,
+			Season::Summer => Season::Autumn,
+			Season::Autumn => Season::Winter,
+			Season::Winter => Season::Spring,
+		}
+	}
+
+	/// How cold the season is, from 0 in summer to 1 in winter, which spreads snow and ice.
+	pub fn chill(&self) -> f64 {
+		match self {
+			Season::Spring => 0.3,
+			Season::Summer => 0.0,
+			Season::Autumn => 0.5,
+			Season::Winter => 1.0,
+		}
+	}
+
+	/// How far rivers and swamps rise above their summer level, where floods from melting snow
+	/// are positive and dry spells negative.
+	pub fn wetness(&self) -> f64 {
+		match self {
+			Season::Spring => 0.5,
+			Season::Summer | Season::Winter => 0.0,
+			Season::Autumn => -0.5,
+		}
+	}
+}
+
+#[cfg(feature = "bevy")]
+impl Season {
+	/// The color of flora in the season: fresh in spring, turning in autumn and bare in winter.
+	pub fn flora_color(&self, flora: Flora) -> Color {
+		let color = flora.get_color();
+		let leafy = matches!(flora, Flora::Tree | Flora::Bush | Flora::Flower);
+		match self {
+			Season::Summer => color,
+			_ if !leafy => color,
+			Season::Spring => color.mix(&Color::srgb(0.6, 0.9, 0.4), 0.3),
+			Season::Autumn => color.mix(&Color::srgb(0.8, 0.4, 0.1), 0.7),
+			Season::Winter => color.mix(&Color::srgb(0.45, 0.4, 0.35), 0.8),
+		}
+	}
+}
+
+/// The layers of a world that change with the season, computed on top of the layers it was
+/// generated with.
+///
+/// Every other layer stays the same all year round.
+#[derive(Clone)]
+#[cfg_attr(feature = "bevy", derive(Resource))]
+pub struct SeasonalLayers {
+	pub season: Season,
+	/// The water, with rivers and swamps flooding or drying up.
+	pub water: Layer<WaterType>,
+	/// The terrain detail, with snow and ice spreading as it gets colder.
+	pub detail: Layer<TerrainDetail>,
+}
+
+impl SeasonalLayers {
+	/// Computes the season-dependent layers from the layers of a generated world, which show
+	/// it in summer.
+	pub fn new(
+		noise_gen: &NoiseGenerator,
+		season: Season,
+		water: &Layer<WaterType>,
+		terrain: &Layer<TerrainFeature>,
+		biome: &Layer<Biome>,
+		detail: &Layer<TerrainDetail>,
+	) -> Self {
+		let mut layers =
+			Self { season, water: Self::water(noise_gen, season, water), detail: detail.clone() };
+		layers.refresh_detail(noise_gen, water, terrain, biome, detail, detail.positions());
+		layers
+	}
+
+	/// Recomputes the seasonal detail of the cells covering the given positions, e.g. after the
+	/// underlying detail changed there.
+	pub fn refresh_detail(
+		&mut self,
+		noise_gen: &NoiseGenerator,
+		water: &Layer<WaterType>,
+		terrain: &Layer<TerrainFeature>,
+		biome: &Layer<Biome>,
+		detail: &Layer<TerrainDetail>,
+		positions: impl IntoIterator<Item = WorldPosition>,
+	) {
+		let season = self.season;
+		for position in positions {
+			let value =
+				noise_gen.get_noise_value(&position, streams::DETAIL) as f64 / u32::MAX as f64;
+			// Lowering the thresholds with the chill grows snow and ice from where they lie in
+			// summer, rather than scattering them anew each season.
+			let threshold =
+				|cover: f64| season.chill() > 0.0 && value > 1.0 - season.chill() * cover;
+			let cell = WorldCell { position, cell_size: detail.scale() };
+			let climate = Self::climate(water, biome, &cell);
+			let cold = matches!(climate, Some(Biome::Tundra | Biome::Snow));
+			// Lakes and rivers of temperate lands only freeze in the depth of winter, and those
+			// of deserts and jungles never do.
+			let freezes = cold
+				|| season == Season::Winter
+					&& matches!(climate, Some(Biome::Grassland | Biome::Forest));
+			let was_water = water.get(position).is_water();
+
+			let tile = match self.water.get(position) {
+				WaterType::Ocean if cold && threshold(0.6) => TerrainDetail::Ice,
+				WaterType::Lake | WaterType::River | WaterType::Swamp
+					if freezes && threshold(0.9) =>
+				{
+					TerrainDetail::Ice
+				}
+				WaterType::None
+					if threshold(Self::snow_cover(biome.get(position), terrain.get(position))) =>
+				{
+					TerrainDetail::Snow
+				}
+				// Flooded land and dried up swamps lose what lay on them.
+				new_water if new_water.is_water() != was_water => TerrainDetail::None,
+				_ => detail.get(position),
+			};
+			self.detail.set(position, tile);
+		}
+	}
+
+	/// The world in the season, with its season-dependent layers swapped for these.
+	pub fn apply_to(&self, world: &WorldLayers) -> WorldLayers {
+		WorldLayers { water: self.water.clone(), detail: self.detail.clone(), ..world.clone() }
+	}
+
+	/// The biome of the land at the cell, or the most common one along the shores of water,
+	/// which has no biome of its own. Open water away from any shore has no climate.
+	fn climate(water: &Layer<WaterType>, biome: &Layer<Biome>, cell: &WorldCell) -> Option<Biome> {
+		if !water.get(cell.position).is_water() {
+			return Some(biome.get(cell.position));
+		}
+
+		let mut counts: Vec<(Biome, usize)> = Vec::new();
+		for (x, y) in Neighborhood::Moore.offsets() {
+			let position = WorldPosition::new(
+				cell.position.x + x * cell.cell_size,
+				cell.position.y + y * cell.cell_size,
+			);
+			if !biome.contains(position) || water.get(position).is_water() {
+				continue;
+			}
+			let shore = biome.get(position);
+			match counts.iter_mut().find(|(counted, _)| *counted == shore) {
+				Some((_, count)) => *count += 1,
+				None => counts.push((shore, 1)),
+			}
+		}
+		// The first of the most common biomes wins ties, so the climate is stable.
+		counts.iter().rev().max_by_key(|(_, count)| *count).map(|(shore, _)| *shore)
+	}
+
+	/// How much of the land snow covers in the depth of winter.
+	fn snow_cover(biome: Biome, terrain_feature: TerrainFeature) -> f64 {
+		match (biome, terrain_feature) {
+			(Biome::Tundra | Biome::Snow, _) => 0.9,
+			(Biome::Desert | Biome::Jungle, _) => 0.0,
+			(_, TerrainFeature::Mountain) => 0.7,
+			_ => 0.4,
+		}
+	}
+
+	/// Floods the land along rivers and swamps in wet seasons, and dries out swamps in dry ones.
+	fn water(
+		noise_gen: &NoiseGenerator,
+		season: Season,
+		water: &Layer<WaterType>,
+	) -> Layer<WaterType> {
+		let wetness = season.wetness();
+		let mut layer = water.clone();
+		for position in water.positions() {
+			let value =
+				noise_gen.get_noise_value(&position, streams::SEASON) as f64 / u32::MAX as f64;
+			let cell = WorldCell { position, cell_size: water.scale() };
+			let current = water.get(position);
+			if wetness > 0.0 && current == WaterType::None && value < wetness {
+				let inland =
+					|neighbor: WaterType| matches!(neighbor, WaterType::River | WaterType::Swamp);
+				if water.neighbors(&cell, Neighborhood::VonNeumann).any(inland) {
+					layer.set(position, WaterType::Swamp);
+				}
+			} else if wetness < 0.0 && current == WaterType::Swamp && value < -wetness {
+				layer.set(position, WaterType::None);
+			}
+		}
+		layer
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::world::WorldGenConfig;
+
+	fn seasonal(world: &WorldLayers, season: Season) -> SeasonalLayers {
+		SeasonalLayers::new(
+			&NoiseGenerator::new(0),
+			season,
+			&world.water,
+			&world.terrain,
+			&world.biome,
+			&world.detail,
+		)
+	}
+
+	fn count<T: crate::layer::LayerValue>(layer: &Layer<T>, value: T) -> usize {
+		layer.positions().filter(|position| layer.get(*position) == value).count()
+	}
+
+	fn same<T: crate::layer::LayerValue>(a: &Layer<T>, b: &Layer<T>) -> bool {
+		a.positions().all(|position| a.get(position) == b.get(position))
+	}
+
+	#[test]
+	fn summer_is_the_generated_world() {
+		let world = WorldLayers::generate(&WorldGenConfig::default());
+		let summer = seasonal(&world, Season::Summer);
+
+		assert!(same(&summer.water, &world.water));
+		assert!(same(&summer.detail, &world.detail));
+	}
+
+	#[test]
+	fn winter_spreads_snow_and_ice() {
+		let world = WorldLayers::generate(&WorldGenConfig::default());
+		let (summer, winter) = (seasonal(&world, Season::Summer), seasonal(&world, Season::Winter));
+
+		assert!(
+			count(&winter.detail, TerrainDetail::Snow) > count(&summer.detail, TerrainDetail::Snow)
+		);
+		assert!(
+			count(&winter.detail, TerrainDetail::Ice) > count(&summer.detail, TerrainDetail::Ice)
+		);
+		// Whatever is snowed in in summer stays so in winter.
+		for position in summer.detail.positions() {
+			if summer.detail.get(position) == TerrainDetail::Snow {
+				assert_eq!(winter.detail.get(position), TerrainDetail::Snow);
+			}
+		}
+	}
+
+	#[test]
+	fn warm_waters_only_freeze_in_winter() {
+		let world = WorldLayers::generate(&WorldGenConfig::default());
+		let summer = seasonal(&world, Season::Summer);
+		for season in [Season::Spring, Season::Autumn, Season::Winter] {
+			let layers = seasonal(&world, season);
+			for position in layers.detail.positions() {
+				let cell = WorldCell { position, cell_size: layers.detail.scale() };
+				let climate = SeasonalLayers::climate(&world.water, &world.biome, &cell);
+				if layers.detail.get(position) == TerrainDetail::Ice
+					&& summer.detail.get(position) != TerrainDetail::Ice
+					&& !matches!(climate, Some(Biome::Tundra | Biome::Snow))
+				{
+					assert_eq!(season, Season::Winter);
+					assert!(matches!(climate, Some(Biome::Grassland | Biome::Forest)));
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn rivers_flood_in_spring_and_swamps_dry_in_autumn() {
+		let world = WorldLayers::generate(&WorldGenConfig::default());
+		let swamps = |season| count(&seasonal(&world, season).water, WaterType::Swamp);
+
+		assert!(swamps(Season::Spring) > swamps(Season::Summer));
+		assert!(swamps(Season::Autumn) < swamps(Season::Summer));
+		// Oceans and lakes keep their shores all year round.
+		for season in Season::ALL {
+			let water = seasonal(&world, season).water;
+			assert_eq!(count(&water, WaterType::Ocean), count(&world.water, WaterType::Ocean));
+			assert_eq!(count(&water, WaterType::Lake), count(&world.water, WaterType::Lake));
+		}
+	}
+
+	#[test]
+	fn seasons_leave_the_world_alone() {
+		let world = WorldLayers::generate(&WorldGenConfig::default());
+		let summer_world = seasonal(&world, Season::Summer).apply_to(&world);
+		let winter_world = seasonal(&world, Season::Winter).apply_to(&world);
+
+		assert!(!same(&summer_world.detail, &winter_world.detail));
+		assert!(same(&summer_world.terrain, &winter_world.terrain));
+		assert!(same(&summer_world.biome, &winter_world.biome));
+		assert!(same(&summer_world.flora, &winter_world.flora));
+		assert!(same(&summer_world.mineral, &winter_world.mineral));
+		assert!(same(&summer_world.urban, &winter_world.urban));
+		assert!(same(&summer_world.special, &winter_world.special));
+		assert!(same(&summer_world.territory, &winter_world.territory));
+	}
+
+	#[test]
+	fn refreshing_catches_up_on_changed_detail() {
+		let mut world = WorldLayers::generate(&WorldGenConfig::default());
+		let mut summer = seasonal(&world, Season::Summer);
+		let position = world
+			.detail
+			.positions()
+			.find(|position| {
+				!world.water.get(*position).is_water()
+					&& world.detail.get(*position) != TerrainDetail::Rock
+			})
+			.unwrap();
+		world.detail.set(position, TerrainDetail::Rock);
+		assert_ne!(summer.detail.get(position), TerrainDetail::Rock);
+
+		summer.refresh_detail(
+			&NoiseGenerator::new(0),
+			&world.water,
+			&world.terrain,
+			&world.biome,
+			&world.detail,
+			[position],
+		);
+		assert!(same(&summer.detail, &world.detail));
+	}
+}
@@ -0,0 +1,393 @@
+use crate::change::ChangeLog;
+use crate::disaster::{Disaster, Disasters, Surface};
+use crate::layer::base::{streams, NoiseGenerator};
+use crate::layer::layers::biome::Biome;
+#[cfg(feature = "bevy")]
+use crate::layer::layers::detail::TerrainDetail;
+#[cfg(feature = "bevy")]
+use crate::layer::layers::flora::Flora;
+use crate::layer::layers::terrain::TerrainFeature;
+use crate::layer::layers::urban::Urban;
+use crate::layer::layers::water::WaterType;
+use crate::layer::{Layer, Neighborhood, WorldCell, WorldPosition};
+use crate::world::WorldLayers;
+#[cfg(feature = "bevy")]
+use bevy::prelude::*;
+use rand::prelude::*;
+
+/// Evolves the settlements of a world year by year: houses spread along water and fertile
+/// plains and grow into farms and cities, while isolated settlements fall into ruin.
+///
+/// The simulation rewrites an urban layer it is handed rather than owning one, and reads the
+/// [Ground] it is handed every year, so disasters, edits and other changes to the layers carry
+/// over into the following years. Every year draws from its own seeded generator, so the same
+/// world always ends up the same after the same number of years, however they were stepped
+/// through.
+#[derive(Clone)]
+#[cfg_attr(feature = "bevy", derive(Resource))]
+pub struct UrbanSimulation {
+	noise_gen: NoiseGenerator,
+	year: u64,
+}
+
+/// The layers settlements grow on, which the [UrbanSimulation] reads but never rewrites.
+#[derive(Clone, Copy)]
+pub struct Ground<'a> {
+	pub water: &'a Layer<WaterType>,
+	pub terrain: &'a Layer<TerrainFeature>,
+	pub biome: &'a Layer<Biome>,
+}
+
+impl WorldLayers {
+	/// Borrows the layers settlements grow on.
+	pub fn ground(&self) -> Ground<'_> {
+		Ground { water: &self.water, terrain: &self.terrain, biome: &self.biome }
+	}
+}
+
+impl<'a> Surface<'a> {
+	/// The layers of the surface settlements grow on.
+	pub fn ground(&self) -> Ground<'a> {
+		Ground { water: self.water, terrain: self.terrain, biome: self.biome }
+	}
+}
+
+impl UrbanSimulation {
+	/// Starts a simulation in year 0.
+	pub fn new(noise_gen: &NoiseGenerator) -> Self {
+		Self { noise_gen: noise_gen.clone(), year: 0 }
+	}
+
+	/// The years simulated so far.
+	pub fn year(&self) -> u64 {
+		self.year
+	}
+
+	/// Simulates the settlements for the given number of years at once.
+	pub fn fast_forward(
+		&mut self,
+		years: u64,
+		ground: Ground,
+		urban: &mut Layer<Urban>,
+		log: &mut ChangeLog,
+	) {
+		for _ in 0..years {
+			self.step(ground, urban, log);
+		}
+	}
+
+	/// Simulates one year of the world: its settlements change first, then the disasters of
+	/// the year strike.
+	pub fn advance(
+		&mut self,
+		disasters: &Disasters,
+		surface: &mut Surface,
+		log: &mut ChangeLog,
+	) -> Vec<Disaster> {
+		let year = self.year;
+		self.step(surface.ground(), surface.urban, log);
+		disasters.strike(year, surface, log)
+	}
+
+	/// Simulates the settlements for one year, where every cell changes based on its neighbors
+	/// at the start of the year.
+	pub fn step(&mut self, ground: Ground, urban: &mut Layer<Urban>, log: &mut ChangeLog) {
+		let seed = self
+			.noise_gen
+			.get_noise_value(&WorldPosition::new(self.year as i64, 0), streams::SIMULATION);
+		let mut rng = StdRng::seed_from_u64(seed as u64);
+		let cell_size = urban.scale();
+
+		let mut changes = Vec::new();
+		for position in urban.positions() {
+			// Draw for every cell, so one cell's roll never depends on what its neighbors do.
+			let roll: f64 = rng.gen();
+			let cell = WorldCell { position, cell_size };
+			let settled_neighbors =
+				urban.neighbors(&cell, Neighborhood::Moore).filter(is_settled).count();
+			let near_water = ground.is_near_water(&cell);
+			let current = urban.get(position);
+			let next_urban = match current {
+				// A lone settlement only draws newcomers where there is water nearby.
+				Urban::None | Urban::Ruin
+					if (settled_neighbors >= 2 || (settled_neighbors == 1 && near_water))
+						&& ground.is_buildable(position) =>
+				{
+					let chance = 0.02
+						* settled_neighbors as f64
+						* (1.0 + f64::from(ground.is_fertile(position)))
+						* (1.0 + f64::from(near_water));
+					if roll < chance {
+						Urban::House
+					} else {
+						current
+					}
+				}
+				Urban::House | Urban::Farm | Urban::City if settled_neighbors == 0 => {
+					if roll < 0.1 {
+						Urban::Ruin
+					} else {
+						current
+					}
+				}
+				Urban::House if ground.is_fertile(position) && roll < 0.1 => Urban::Farm,
+				Urban::House | Urban::Farm
+					if settled_neighbors >= 5 && near_water && roll < 0.02 =>
+				{
+					Urban::City
+				}
+				_ => current,
+			};
+			if next_urban != current {
+				changes.push((position, next_urban));
+			}
+		}
+
+		for (position, value) in changes {
+			log.set(urban, position, value);
+		}
+		self.year += 1;
+	}
+}
+
+impl Ground<'_> {
+	/// Whether houses may be built on the cell, which takes dry, level land.
+	fn is_buildable(&self, position: WorldPosition) -> bool {
+		!self.water.get(position).is_water()
+			&& matches!(self.terrain.get(position), TerrainFeature::Plains | TerrainFeature::Valley)
+	}
+
+	/// Whether the cell is farmland, with grass or forest on level land.
+	fn is_fertile(&self, position: WorldPosition) -> bool {
+		self.is_buildable(position)
+			&& matches!(self.biome.get(position), Biome::Grassland | Biome::Forest)
+	}
+
+	/// Whether the cell borders any water to draw from or trade over.
+	fn is_near_water(&self, cell: &WorldCell) -> bool {
+		self.water.neighbors(cell, Neighborhood::Moore).any(|water| water.is_water())
+	}
+}
+
+/// Whether people live in the cell, which lets settlements spread into the cells around it.
+fn is_settled(urban: &Urban) -> bool {
+	matches!(urban, Urban::House | Urban::Farm | Urban::City | Urban::Port)
+}
+
+/// Paces the [UrbanSimulation] while the game runs.
+#[cfg(feature = "bevy")]
+#[derive(Resource, Clone, Debug)]
+pub struct SimulationClock {
+	/// Ticks once per simulated year.
+	pub timer: Timer,
+	pub running: bool,
+	/// The years to simulate on the next update, regardless of the timer.
+	pending: u64,
+}
+
+#[cfg(feature = "bevy")]
+impl SimulationClock {
+	/// Simulates the given number of years on the next update, even while paused.
+	pub fn fast_forward(&mut self, years: u64) {
+		self.pending += years;
+	}
+}
+
+#[cfg(feature = "bevy")]
+impl Default for SimulationClock {
+	fn default() -> Self {
+		Self { timer: Timer::from_seconds(1.0, TimerMode::Repeating), running: false, pending: 0 }
+	}
+}
+
+/// Advances the [UrbanSimulation] and its [Disasters] once per tick of the running
+/// [SimulationClock], recording every change in the [ChangeLog] resource.
+///
+/// The simulation only runs while the layers it rewrites are enabled.
+#[cfg(feature = "bevy")]
+#[allow(clippy::too_many_arguments)]
+pub fn advance_simulation(
+	time: Res<Time>,
+	mut clock: ResMut<SimulationClock>,
+	simulation: Option<ResMut<UrbanSimulation>>,
+	disasters: Option<Res<Disasters>>,
+	water: Option<Res<Layer<WaterType>>>,
+	terrain: Option<Res<Layer<TerrainFeature>>>,
+	biome: Option<Res<Layer<Biome>>>,
+	flora: Option<ResMut<Layer<Flora>>>,
+	detail: Option<ResMut<Layer<TerrainDetail>>>,
+	urban: Option<ResMut<Layer<Urban>>>,
+	mut log: ResMut<ChangeLog>,
+) {
+	let mut years = std::mem::take(&mut clock.pending);
+	if clock.running {
+		clock.timer.tick(time.delta());
+		years += u64::from(clock.timer.times_finished_this_tick());
+	}
+	if years == 0 {
+		return;
+	}
+	let (
+		Some(mut simulation),
+		Some(disasters),
+		Some(water),
+		Some(terrain),
+		Some(biome),
+		Some(mut flora),
+		Some(mut detail),
+		Some(mut urban),
+	) = (simulation, disasters, water, terrain, biome, flora, detail, urban)
+	else {
+		return;
+	};
+
+	let mut surface = Surface {
+		water: &water,
+		terrain: &terrain,
+		biome: &biome,
+		flora: &mut flora,
+		detail: &mut detail,
+		urban: &mut urban,
+	};
+	for _ in 0..years {
+		for disaster in simulation.advance(&disasters, &mut surface, &mut log) {
+			debug!("{disaster:?}");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::invariant::Invariants;
+	use crate::world::WorldGenConfig;
+
+	fn count(layer: &Layer<Urban>, urban: impl Fn(Urban) -> bool) -> usize {
+		layer.positions().filter(|position| urban(layer.get(*position))).count()
+	}
+
+	/// A 16x16 world of grassland plains with a river along the column x = 0, and the given
+	/// settlements.
+	fn valley(
+		settlements: &[(i64, i64, Urban)],
+	) -> (Layer<WaterType>, Layer<TerrainFeature>, Layer<Biome>, Layer<Urban>) {
+		let mut water = Layer::new_base32(0, 4);
+		let terrain = Layer::new_base32(0, 4);
+		let mut biome = Layer::new_base32(0, 4);
+		let mut urban = Layer::new_base32(0, 4);
+		for position in biome.positions() {
+			biome.set(position, Biome::Grassland);
+		}
+		for y in -8..8 {
+			water.set(WorldPosition::new(0, y), WaterType::River);
+		}
+		for (x, y, settlement) in settlements {
+			urban.set(WorldPosition::new(*x, *y), *settlement);
+		}
+		(water, terrain, biome, urban)
+	}
+
+	#[test]
+	fn simulation_is_deterministic() {
+		let world = WorldLayers::generate(&WorldGenConfig::default());
+		let noise_gen = NoiseGenerator::new(0);
+		let mut log = ChangeLog::new();
+		let mut stepped = UrbanSimulation::new(&noise_gen);
+		let mut stepped_urban = world.urban.clone();
+		stepped.fast_forward(5, world.ground(), &mut stepped_urban, &mut log);
+		stepped.fast_forward(15, world.ground(), &mut stepped_urban, &mut log);
+		let mut fast_forwarded = UrbanSimulation::new(&noise_gen);
+		let mut fast_forwarded_urban = world.urban.clone();
+		fast_forwarded.fast_forward(
+			20,
+			world.ground(),
+			&mut fast_forwarded_urban,
+			&mut ChangeLog::new(),
+		);
+
+		assert_eq!(stepped.year(), 20);
+		assert!(stepped_urban
+			.positions()
+			.all(|position| stepped_urban.get(position) == fast_forwarded_urban.get(position)));
+		// The log holds exactly the way from the generated settlements to the simulated ones.
+		let mut replayed = world.clone();
+		replayed.apply_changes(log.changes(), &mut ChangeLog::new());
+		assert!(stepped_urban
+			.positions()
+			.all(|position| stepped_urban.get(position) == replayed.urban.get(position)));
+	}
+
+	#[test]
+	fn settlements_grow_along_the_river() {
+		let (water, terrain, biome, mut urban) =
+			valley(&[(1, 0, Urban::House), (1, 1, Urban::House)]);
+		let ground = Ground { water: &water, terrain: &terrain, biome: &biome };
+		UrbanSimulation::new(&NoiseGenerator::new(1)).fast_forward(
+			100,
+			ground,
+			&mut urban,
+			&mut ChangeLog::new(),
+		);
+
+		assert!(count(&urban, |urban| is_settled(&urban)) > 20);
+		assert!(count(&urban, |urban| urban == Urban::Farm) > 0);
+		assert!(count(&urban, |urban| urban == Urban::City) > 0);
+		// Nothing is ever built in the river itself.
+		assert!((-8..8).all(|y| urban.get(WorldPosition::new(0, y)) == Urban::None));
+	}
+
+	#[test]
+	fn isolated_settlements_fall_into_ruin() {
+		let (water, terrain, biome, mut urban) =
+			valley(&[(-6, -6, Urban::House), (5, 5, Urban::City)]);
+		let ground = Ground { water: &water, terrain: &terrain, biome: &biome };
+		UrbanSimulation::new(&NoiseGenerator::new(1)).fast_forward(
+			100,
+			ground,
+			&mut urban,
+			&mut ChangeLog::new(),
+		);
+
+		assert_eq!(urban.get(WorldPosition::new(-6, -6)), Urban::Ruin);
+		assert_eq!(urban.get(WorldPosition::new(5, 5)), Urban::Ruin);
+		assert_eq!(count(&urban, |urban| urban != Urban::None), 2);
+	}
+
+	#[test]
+	fn settlements_keep_out_of_water_painted_between_years() {
+		let (mut water, terrain, biome, mut urban) =
+			valley(&[(1, 0, Urban::House), (1, 1, Urban::House)]);
+		let mut simulation = UrbanSimulation::new(&NoiseGenerator::new(1));
+		let ground = Ground { water: &water, terrain: &terrain, biome: &biome };
+		simulation.fast_forward(5, ground, &mut urban, &mut ChangeLog::new());
+		// A lake floods the east bank of the river.
+		for position in water.positions().filter(|position| position.x > 2) {
+			water.set(position, WaterType::Lake);
+		}
+		let ground = Ground { water: &water, terrain: &terrain, biome: &biome };
+		simulation.fast_forward(100, ground, &mut urban, &mut ChangeLog::new());
+
+		assert!(count(&urban, |urban| is_settled(&urban)) > 20);
+		assert!(urban
+			.positions()
+			.filter(|position| position.x > 2)
+			.all(|position| urban.get(position) == Urban::None));
+	}
+
+	#[test]
+	fn simulated_worlds_uphold_the_invariants() {
+		let mut world =
+			WorldLayers::generate(&WorldGenConfig { seed: 3, ..WorldGenConfig::default() });
+		let noise_gen = NoiseGenerator::new(3);
+		let mut simulation = UrbanSimulation::new(&noise_gen);
+		let disasters = Disasters::new(&noise_gen, &world.special);
+		let mut log = ChangeLog::new();
+		let struck: usize = (0..50)
+			.map(|_| simulation.advance(&disasters, &mut world.surface(), &mut log).len())
+			.sum();
+
+		assert!(struck > 0);
+		let report = Invariants::default().check(&world);
+		assert!(report.is_ok(), "{report}");
+	}
+}
@@ -0,0 +1,258 @@
+use crate::change::{CellValue, WorldValue};
+use crate::layer::{GridPosition, Layer, WorldPosition};
+use crate::world::LayerKind;
+use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use std::fmt;
+#[cfg(feature = "serde")]
+use std::path::Path;
+
+/// An error raised while loading a [Stamp].
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub enum StampError {
+	Io(std::io::Error),
+	Parse(serde_json::Error),
+	/// A symbol of a patch paints a value of another layer than the patch's.
+	WrongLayer {
+		layer: LayerKind,
+		symbol: char,
+	},
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for StampError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			StampError::Io(error) => write!(f, "failed to read stamp: {error}"),
+			StampError::Parse(error) => write!(f, "failed to parse stamp: {error}"),
+			StampError::WrongLayer { layer, symbol } => {
+				write!(f, "symbol {symbol:?} of a {layer:?} patch paints another layer")
+			}
+		}
+	}
+}
+
+#[cfg(feature = "serde")]
+impl std::error::Error for StampError {}
+
+/// How the cells of a [Patch] blend with the generated ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Blend {
+	/// The patch replaces whatever was generated.
+	#[default]
+	Replace,
+	/// The patch only fills cells left empty, i.e. holding the default value of the layer.
+	Fill,
+}
+
+/// A hand-made block of cells of one layer, drawn as rows of symbols.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Patch {
+	pub layer: LayerKind,
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub blend: Blend,
+	/// The column and row of the cell placed on the anchor of the stamp, or `None` for the
+	/// middle of the patch.
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub pivot: Option<(usize, usize)>,
+	/// The value each symbol paints, where symbols missing from it leave their cell alone.
+	pub legend: HashMap<char, CellValue>,
+	/// The cells of the patch, a symbol per cell, from the top row down.
+	pub rows: Vec<String>,
+}
+
+impl Patch {
+	/// The values the patch paints, by their offset in cells from the pivot once the patch is
+	/// turned counterclockwise by the number of quarter turns.
+	pub fn cells(&self, rotation: u8) -> Vec<((i64, i64), CellValue)> {
+		let width = self.rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+		let (pivot_x, pivot_y) = self.pivot.unwrap_or((width / 2, self.rows.len() / 2));
+
+		let mut cells = Vec::new();
+		for (row, symbols) in self.rows.iter().enumerate() {
+			for (column, symbol) in symbols.chars().enumerate() {
+				let Some(value) = self.legend.get(&symbol) else {
+					continue;
+				};
+				// Rows run down the page, while the world's y axis points up.
+				let (mut x, mut y) = (column as i64 - pivot_x as i64, pivot_y as i64 - row as i64);
+				for _ in 0..rotation % 4 {
+					(x, y) = (-y, x);
+				}
+				cells.push(((x, y), *value));
+			}
+		}
+		cells
+	}
+}
+
+/// Hand-made patches of a few layers placed at a fixed spot of an otherwise generated world, like
+/// a capital city or a starting island.
+///
+/// Stamps are part of the [crate::world::WorldGenConfig], which stamps each layer as soon as it
+/// is generated, so the layers depending on it are generated around the stamp.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stamp {
+	/// Where the pivots of the patches land.
+	pub anchor: WorldPosition,
+	/// The number of quarter turns, counterclockwise, the patches are turned by around their
+	/// pivots.
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub rotation: u8,
+	pub patches: Vec<Patch>,
+}
+
+impl Stamp {
+	/// Paints the patches of the layer onto it, skipping cells outside the world.
+	pub fn apply<T: WorldValue>(&self, layer: &mut Layer<T>) {
+		let anchor = layer.get_grid_position(self.anchor);
+		for patch in self.patches.iter().filter(|patch| patch.layer == T::KIND) {
+			for ((x, y), value) in patch.cells(self.rotation) {
+				let cell = GridPosition::new(anchor.x + x, anchor.y + y, anchor.scale);
+				let Some(value) = T::from_cell(value) else {
+					continue;
+				};
+				if !layer.contains(cell.into())
+					|| patch.blend == Blend::Fill && layer.get_grid(cell) != T::default()
+				{
+					continue;
+				}
+				layer.set_grid(cell, value);
+			}
+		}
+	}
+
+	/// Parses a stamp from JSON, checking that every patch only paints its own layer.
+	#[cfg(feature = "serde")]
+	pub fn from_json(json: &str) -> Result<Self, StampError> {
+		let stamp: Self = serde_json::from_str(json).map_err(StampError::Parse)?;
+		for patch in &stamp.patches {
+			if let Some((symbol, _)) =
+				patch.legend.iter().find(|(_, value)| value.kind() != patch.layer)
+			{
+				return Err(StampError::WrongLayer { layer: patch.layer, symbol: *symbol });
+			}
+		}
+		Ok(stamp)
+	}
+
+	/// Loads a stamp from a JSON file.
+	#[cfg(feature = "serde")]
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, StampError> {
+		Self::from_json(&std::fs::read_to_string(path).map_err(StampError::Io)?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::layer::layers::urban::Urban;
+	use crate::layer::layers::water::WaterType;
+	use crate::names::PlaceKind;
+	use crate::world::{WorldGenConfig, WorldLayers};
+
+	fn patch(layer: LayerKind, legend: &[(char, CellValue)], rows: &[&str]) -> Patch {
+		Patch {
+			layer,
+			blend: Blend::Replace,
+			pivot: None,
+			legend: legend.iter().copied().collect(),
+			rows: rows.iter().map(|row| String::from(*row)).collect(),
+		}
+	}
+
+	#[test]
+	fn patches_turn_around_their_pivot() {
+		let patch = patch(
+			LayerKind::Urban,
+			&[('T', CellValue::Urban(Urban::Temple))],
+			&[".T.", "...", "..."],
+		);
+
+		assert_eq!(patch.cells(0), vec![((0, 1), CellValue::Urban(Urban::Temple))]);
+		assert_eq!(patch.cells(1), vec![((-1, 0), CellValue::Urban(Urban::Temple))]);
+		assert_eq!(patch.cells(2), vec![((0, -1), CellValue::Urban(Urban::Temple))]);
+		assert_eq!(patch.cells(7), patch.cells(3));
+	}
+
+	#[test]
+	fn filling_patches_keep_generated_cells() {
+		let mut layer = Layer::new_base32(0, 4);
+		layer.set(WorldPosition::new(0, 0), Urban::Farm);
+		let mut fill = patch(LayerKind::Urban, &[('H', CellValue::Urban(Urban::House))], &["HH"]);
+		fill.blend = Blend::Fill;
+		fill.pivot = Some((0, 0));
+		let stamp = Stamp { anchor: WorldPosition::new(0, 0), rotation: 0, patches: vec![fill] };
+
+		stamp.apply(&mut layer);
+		assert_eq!(layer.get(WorldPosition::new(0, 0)), Urban::Farm);
+		assert_eq!(layer.get(WorldPosition::new(1, 0)), Urban::House);
+		// Patches of other layers leave the layer alone.
+		let mut water = Layer::<WaterType>::new_base32(0, 4);
+		stamp.apply(&mut water);
+		assert!(water.positions().all(|position| water.get(position) == WaterType::None));
+	}
+
+	#[test]
+	fn stamped_layers_are_generated_around() {
+		let plain = WorldLayers::generate(&WorldGenConfig::default());
+		// An island with a capital, out in the ocean.
+		let anchor = plain
+			.water
+			.positions()
+			.find(|position| {
+				plain.water.get(*position) == WaterType::Ocean
+					&& (position.x.abs() > 128 || position.y.abs() > 128)
+			})
+			.unwrap();
+		let stamp = Stamp {
+			anchor,
+			rotation: 0,
+			patches: vec![
+				patch(LayerKind::Water, &[('#', CellValue::Water(WaterType::None))], &["#"]),
+				patch(LayerKind::Urban, &[('C', CellValue::Urban(Urban::City))], &["C"]),
+			],
+		};
+		let config = WorldGenConfig { stamps: vec![stamp], ..WorldGenConfig::default() };
+		let world = WorldLayers::generate(&config);
+
+		assert_eq!(world.water.get(anchor), WaterType::None);
+		assert_eq!(world.urban.get(anchor), Urban::City);
+		// The capital is named and rules the island like any generated city.
+		let cities = |world: &WorldLayers| world.names.of_kind(PlaceKind::City).count();
+		assert!(cities(&world) > cities(&plain));
+		assert!(world.territory.get(anchor).is_claimed());
+		// Far from the stamp, the world is what it would have been.
+		let far = WorldPosition::new(-anchor.x.signum() * 256, -anchor.y.signum() * 256);
+		assert_eq!(world.flora.get(far), plain.flora.get(far));
+		assert_eq!(world.detail.get(far), plain.detail.get(far));
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn stamps_load_from_json() {
+		let json = r##"{
+			"anchor": { "x": 64, "y": -32 },
+			"rotation": 1,
+			"patches": [{
+				"layer": "Urban",
+				"legend": { "C": { "Urban": "City" }, "h": { "Urban": "House" } },
+				"rows": ["hCh"]
+			}]
+		}"##;
+		let stamp = Stamp::from_json(json).unwrap();
+		assert_eq!(stamp.anchor, WorldPosition::new(64, -32));
+		assert_eq!(stamp.patches[0].blend, Blend::Replace);
+		assert_eq!(stamp.patches[0].cells(stamp.rotation).len(), 3);
+
+		let wrong = json.replace(r#""h": { "Urban": "House" }"#, r#""h": { "Flora": "Tree" }"#);
+		assert!(matches!(
+			Stamp::from_json(&wrong),
+			Err(StampError::WrongLayer { layer: LayerKind::Urban, symbol: 'h' })
+		));
+	}
+}
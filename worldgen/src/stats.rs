@@ -0,0 +1,348 @@
+#[cfg(feature = "bevy")]
+use crate::change::LayerResources;
+use crate::layer::layers::biome::Biome;
+use crate::layer::layers::detail::TerrainDetail;
+use crate::layer::layers::flora::Flora;
+use crate::layer::layers::mineral::Mineral;
+use crate::layer::layers::special::Special;
+use crate::layer::layers::terrain::TerrainFeature;
+use crate::layer::layers::territory::TerritoryId;
+use crate::layer::layers::urban::Urban;
+use crate::layer::layers::water::WaterType;
+use crate::layer::{Layer, LayerValue, Neighborhood};
+use crate::world::{LayerKind, WorldLayers};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// The size distribution of the connected regions of one variant, in cells.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RegionStats {
+	pub count: usize,
+	pub smallest: usize,
+	pub median: usize,
+	pub largest: usize,
+	pub mean: f64,
+}
+
+impl RegionStats {
+	fn from_sizes(mut sizes: Vec<usize>) -> Self {
+		if sizes.is_empty() {
+			return Self::default();
+		}
+
+		sizes.sort_unstable();
+		Self {
+			count: sizes.len(),
+			smallest: sizes[0],
+			median: sizes[sizes.len() / 2],
+			largest: sizes[sizes.len() - 1],
+			mean: sizes.iter().sum::<usize>() as f64 / sizes.len() as f64,
+		}
+	}
+}
+
+/// Statistics on the variants of a single layer, keyed by variant name.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LayerStats {
+	pub layer: LayerKind,
+	pub cells: usize,
+	/// How many cells hold each variant.
+	pub histogram: BTreeMap<String, usize>,
+	/// The share of cells holding each variant, in percent.
+	pub coverage: BTreeMap<String, f64>,
+	/// The orthogonally connected regions of each variant.
+	pub regions: BTreeMap<String, RegionStats>,
+}
+
+impl LayerStats {
+	pub fn compute<T: LayerValue + fmt::Debug>(layer_kind: LayerKind, layer: &Layer<T>) -> Self {
+		let mut histogram = BTreeMap::new();
+		let mut sizes: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+		for region in layer.regions(Neighborhood::VonNeumann) {
+			let name = format!("{:?}", layer.get(region[0]));
+			*histogram.entry(name.clone()).or_default() += region.len();
+			sizes.entry(name).or_default().push(region.len());
+		}
+
+		let cells = histogram.values().sum();
+		let coverage = histogram
+			.iter()
+			.map(|(name, count)| (name.clone(), *count as f64 / cells as f64 * 100.0))
+			.collect();
+		let regions = sizes
+			.into_iter()
+			.map(|(name, sizes)| (name, RegionStats::from_sizes(sizes)))
+			.collect();
+		Self { layer: layer_kind, cells, histogram, coverage, regions }
+	}
+}
+
+/// How often the variants of two layers coincide, counted per cell of the finer layer.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct JointStats {
+	pub rows: LayerKind,
+	pub columns: LayerKind,
+	/// The number of cells holding each pair of variants, by row and then column variant.
+	pub counts: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+impl JointStats {
+	pub fn compute<A: LayerValue + fmt::Debug, B: LayerValue + fmt::Debug>(
+		(rows, row_layer): (LayerKind, &Layer<A>),
+		(columns, column_layer): (LayerKind, &Layer<B>),
+	) -> Self {
+		let positions = if row_layer.scale() <= column_layer.scale() {
+			row_layer.positions()
+		} else {
+			column_layer.positions()
+		};
+
+		let mut counts: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+		for position in positions {
+			let row = format!("{:?}", row_layer.get(position));
+			let column = format!("{:?}", column_layer.get(position));
+			*counts.entry(row).or_default().entry(column).or_default() += 1;
+		}
+		Self { rows, columns, counts }
+	}
+}
+
+/// The distribution report of a generated world.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WorldStats {
+	pub layers: Vec<LayerStats>,
+	pub joints: Vec<JointStats>,
+}
+
+impl WorldStats {
+	/// Computes the statistics of every layer, along with the joint distributions of the
+	/// layers that depend on each other the most.
+	pub fn compute(world: &WorldLayers) -> Self {
+		Layers {
+			water: Some(&world.water),
+			terrain: Some(&world.terrain),
+			biome: Some(&world.biome),
+			detail: Some(&world.detail),
+			flora: Some(&world.flora),
+			mineral: Some(&world.mineral),
+			urban: Some(&world.urban),
+			special: Some(&world.special),
+			territory: Some(&world.territory),
+		}
+		.stats()
+	}
+
+	/// Computes the statistics of the enabled layer resources, as they are now rather than as
+	/// they were generated, skipping the joint distributions of disabled layers.
+	#[cfg(feature = "bevy")]
+	pub fn of_resources(layers: &LayerResources) -> Self {
+		Layers {
+			water: layers.water.as_deref(),
+			terrain: layers.terrain.as_deref(),
+			biome: layers.biome.as_deref(),
+			detail: layers.detail.as_deref(),
+			flora: layers.flora.as_deref(),
+			mineral: layers.mineral.as_deref(),
+			urban: layers.urban.as_deref(),
+			special: layers.special.as_deref(),
+			territory: layers.territory.as_deref(),
+		}
+		.stats()
+	}
+
+	/// Gets the statistics of the given layer.
+	pub fn layer(&self, layer_kind: LayerKind) -> Option<&LayerStats> {
+		self.layers.iter().find(|stats| stats.layer == layer_kind)
+	}
+
+	/// Exports the statistics as pretty-printed JSON.
+	#[cfg(feature = "serde")]
+	pub fn to_json(&self) -> String {
+		serde_json::to_string_pretty(self).expect("statistics always serialize")
+	}
+}
+
+/// The layers to compute statistics of, where disabled layers are missing.
+struct Layers<'a> {
+	water: Option<&'a Layer<WaterType>>,
+	terrain: Option<&'a Layer<TerrainFeature>>,
+	biome: Option<&'a Layer<Biome>>,
+	detail: Option<&'a Layer<TerrainDetail>>,
+	flora: Option<&'a Layer<Flora>>,
+	mineral: Option<&'a Layer<Mineral>>,
+	urban: Option<&'a Layer<Urban>>,
+	special: Option<&'a Layer<Special>>,
+	territory: Option<&'a Layer<TerritoryId>>,
+}
+
+impl Layers<'_> {
+	fn stats(&self) -> WorldStats {
+		let layers = [
+			self.water.map(|water| LayerStats::compute(LayerKind::Water, water)),
+			self.terrain.map(|terrain| LayerStats::compute(LayerKind::Terrain, terrain)),
+			self.biome.map(|biome| LayerStats::compute(LayerKind::Biome, biome)),
+			self.detail.map(|detail| LayerStats::compute(LayerKind::Detail, detail)),
+			self.flora.map(|flora| LayerStats::compute(LayerKind::Flora, flora)),
+			self.mineral.map(|mineral| LayerStats::compute(LayerKind::Mineral, mineral)),
+			self.urban.map(|urban| LayerStats::compute(LayerKind::Urban, urban)),
+			self.special.map(|special| LayerStats::compute(LayerKind::Special, special)),
+			self.territory
+				.map(|territory| LayerStats::compute(LayerKind::Territory, territory)),
+		];
+		let joints = [
+			self.flora.zip(self.biome).map(|(flora, biome)| {
+				JointStats::compute((LayerKind::Flora, flora), (LayerKind::Biome, biome))
+			}),
+			self.detail.zip(self.biome).map(|(detail, biome)| {
+				JointStats::compute((LayerKind::Detail, detail), (LayerKind::Biome, biome))
+			}),
+			self.mineral.zip(self.terrain).map(|(mineral, terrain)| {
+				JointStats::compute((LayerKind::Mineral, mineral), (LayerKind::Terrain, terrain))
+			}),
+			self.urban.zip(self.mineral).map(|(urban, mineral)| {
+				JointStats::compute((LayerKind::Urban, urban), (LayerKind::Mineral, mineral))
+			}),
+			self.special.zip(self.terrain).map(|(special, terrain)| {
+				JointStats::compute((LayerKind::Special, special), (LayerKind::Terrain, terrain))
+			}),
+		];
+		WorldStats {
+			layers: layers.into_iter().flatten().collect(),
+			joints: joints.into_iter().flatten().collect(),
+		}
+	}
+}
+
+/// Prints the statistics as plain text tables.
+impl fmt::Display for WorldStats {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for stats in &self.layers {
+			writeln!(f, "{:?} ({} cells)", stats.layer, stats.cells)?;
+			writeln!(
+				f,
+				"  {:<12} {:>8} {:>9} {:>8} {:>9} {:>7} {:>8}",
+				"variant", "cells", "coverage", "regions", "smallest", "median", "largest"
+			)?;
+			for (name, count) in &stats.histogram {
+				let regions = &stats.regions[name];
+				writeln!(
+					f,
+					"  {:<12} {:>8} {:>8.1}% {:>8} {:>9} {:>7} {:>8}",
+					name,
+					count,
+					stats.coverage[name],
+					regions.count,
+					regions.smallest,
+					regions.median,
+					regions.largest
+				)?;
+			}
+			writeln!(f)?;
+		}
+
+		for joint in &self.joints {
+			let columns: Vec<&String> = joint
+				.counts
+				.values()
+				.flat_map(|row| row.keys())
+				.collect::<BTreeSet<_>>()
+				.into_iter()
+				.collect();
+
+			writeln!(f, "{:?} x {:?}", joint.rows, joint.columns)?;
+			write!(f, "  {:<12}", "")?;
+			for column in &columns {
+				write!(f, " {:>10}", column)?;
+			}
+			writeln!(f)?;
+			for (row, counts) in &joint.counts {
+				write!(f, "  {:<12}", row)?;
+				for column in &columns {
+					write!(f, " {:>10}", counts.get(*column).copied().unwrap_or_default())?;
+				}
+				writeln!(f)?;
+			}
+			writeln!(f)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::layer::layers::biome::Biome;
+	use crate::layer::layers::water::WaterType;
+	use crate::layer::WorldPosition;
+
+	/// A 4x4 water layer with a 2x2 lake in one corner and a single lake cell in another.
+	fn lakes() -> Layer<WaterType> {
+		let mut layer = Layer::new_base32(0, 2);
+		for (x, y) in [(-2, -2), (-1, -2), (-2, -1), (-1, -1), (1, 1)] {
+			layer.set(WorldPosition::new(x, y), WaterType::Lake);
+		}
+		layer
+	}
+
+	#[test]
+	fn layer_stats_count_cells_coverage_and_regions() {
+		let stats = LayerStats::compute(LayerKind::Water, &lakes());
+
+		assert_eq!(stats.cells, 16);
+		assert_eq!(stats.histogram["Lake"], 5);
+		assert_eq!(stats.histogram["None"], 11);
+		assert_eq!(stats.coverage["Lake"], 5.0 / 16.0 * 100.0);
+		assert_eq!(
+			stats.regions["Lake"],
+			RegionStats { count: 2, smallest: 1, median: 4, largest: 4, mean: 2.5 }
+		);
+		assert_eq!(stats.regions["None"].count, 1);
+	}
+
+	#[test]
+	fn joint_stats_count_per_cell_of_the_finer_layer() {
+		let fine = lakes();
+		let mut coarse = Layer::new_base32(1, 2);
+		coarse.set(WorldPosition::new(-2, -2), Biome::Forest);
+
+		let joint = JointStats::compute((LayerKind::Water, &fine), (LayerKind::Biome, &coarse));
+
+		assert_eq!(joint.counts["Lake"]["Forest"], 4);
+		assert_eq!(joint.counts["Lake"]["Desert"], 1);
+		assert_eq!(joint.counts["None"]["Desert"], 11);
+		assert_eq!(joint.counts.values().flat_map(|row| row.values()).sum::<usize>(), 16);
+	}
+
+	#[test]
+	fn world_stats_cover_every_layer() {
+		let world = WorldLayers::generate(&crate::world::WorldGenConfig::default());
+		let stats = WorldStats::compute(&world);
+
+		for layer_kind in LayerKind::ALL {
+			let layer = stats.layer(layer_kind).expect("every layer has statistics");
+			assert_eq!(layer.histogram.values().sum::<usize>(), layer.cells);
+			let coverage: f64 = layer.coverage.values().sum();
+			assert!((coverage - 100.0).abs() < 1e-9);
+		}
+		assert!(stats.to_string().contains("Flora x Biome"));
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn world_stats_export_to_json() {
+		let stats = WorldStats {
+			layers: vec![LayerStats::compute(LayerKind::Water, &lakes())],
+			joints: vec![],
+		};
+
+		let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+
+		assert_eq!(json["layers"][0]["layer"], "Water");
+		assert_eq!(json["layers"][0]["histogram"]["Lake"], 5);
+		assert_eq!(json["layers"][0]["regions"]["Lake"]["count"], 2);
+	}
+}
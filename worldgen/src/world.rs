@@ -0,0 +1,321 @@
+use crate::change::WorldValue;
+use crate::dungeon::Dungeons;
+use crate::layer::base::NoiseGenerator;
+use crate::layer::layers::biome::{generate_biome_layer, Biome};
+use crate::layer::layers::detail::{generate_detail_layer, TerrainDetail};
+use crate::layer::layers::flora::{generate_flora_layer, Flora};
+use crate::layer::layers::mineral::{generate_mineral_layer, Mineral};
+use crate::layer::layers::special::{generate_special_layer, place_volcanoes, Special};
+use crate::layer::layers::terrain::{generate_terrain_layer, TerrainFeature};
+use crate::layer::layers::territory::{generate_territory_layer, TerritoryId};
+use crate::layer::layers::urban::{generate_urban_layer, Urban};
+use crate::layer::layers::water::{generate_water_layer, WaterType};
+use crate::layer::pass::MajorityFilter;
+use crate::layer::{AllGridPositions, Layer, Neighborhood};
+use crate::names::WorldNames;
+use crate::portal::PortalNetwork;
+use crate::stamp::Stamp;
+#[cfg(feature = "bevy")]
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+/// One of the layers making up a generated world.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LayerKind {
+	Water,
+	Terrain,
+	Biome,
+	Detail,
+	Flora,
+	Mineral,
+	Urban,
+	Special,
+	Territory,
+}
+
+impl LayerKind {
+	/// All layers, ordered from bottom to top.
+	pub const ALL: [LayerKind; 9] = [
+		LayerKind::Water,
+		LayerKind::Terrain,
+		LayerKind::Biome,
+		LayerKind::Detail,
+		LayerKind::Flora,
+		LayerKind::Mineral,
+		LayerKind::Urban,
+		LayerKind::Special,
+		LayerKind::Territory,
+	];
+}
+
+/// A preset shaping the overall look of the world.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WorldPreset {
+	#[default]
+	Continents,
+	Archipelago,
+	Pangaea,
+}
+
+impl WorldPreset {
+	/// The factor applied to the water noise, where higher values flood more of the world.
+	pub fn water_level(&self) -> f64 {
+		match self {
+			WorldPreset::Continents => 1.0,
+			WorldPreset::Archipelago => 1.25,
+			WorldPreset::Pangaea => 0.5,
+		}
+	}
+}
+
+/// Configures the generated world.
+///
+/// As a resource of the `WorldGenPlugin`, changing it regenerates the world.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(Resource))]
+pub struct WorldGenConfig {
+	/// The seed for all noise in the world.
+	pub seed: u32,
+	/// The world spans `1 << size` units per side, centered on the origin, up to a size of
+	/// [crate::layer::MAX_WORLD_SIZE].
+	pub size: u32,
+	/// The preset shaping the world.
+	pub preset: WorldPreset,
+	/// The layers to generate; disabled layers are left empty.
+	pub layers: HashSet<LayerKind>,
+	/// The iterations of majority filtering applied to the water, terrain and biome layers,
+	/// removing single-cell islands left by thresholded noise.
+	pub smoothing: usize,
+	/// The highest cost, in terms of [crate::layer::layers::territory::claim_cost], at which
+	/// kingdoms still claim land.
+	pub kingdom_reach: u32,
+	/// Hand-made patches stamped onto the layers as they are generated, so that the layers
+	/// depending on a stamped one are generated around the stamp.
+	pub stamps: Vec<Stamp>,
+}
+
+impl Default for WorldGenConfig {
+	fn default() -> Self {
+		Self {
+			seed: 0,
+			size: 10,
+			preset: WorldPreset::default(),
+			layers: LayerKind::ALL.into_iter().collect(),
+			smoothing: 1,
+			kingdom_reach: 48,
+			stamps: Vec::new(),
+		}
+	}
+}
+
+impl WorldGenConfig {
+	/// Whether the given layer is generated.
+	pub fn is_enabled(&self, kind: LayerKind) -> bool {
+		self.layers.contains(&kind)
+	}
+
+	/// The scale factor of the water, terrain and biome layers (16x16 grid).
+	pub fn base_scale(&self) -> u32 {
+		self.size.saturating_sub(4)
+	}
+
+	/// The scale factor of the detail, flora, mineral, urban and territory layers (64x64 grid).
+	pub fn detail_scale(&self) -> u32 {
+		self.size.saturating_sub(6)
+	}
+
+	/// The scale factor of the special layer (256x256 grid).
+	pub fn special_scale(&self) -> u32 {
+		self.size.saturating_sub(8)
+	}
+
+	/// The layer with the stamps applied.
+	fn stamped<T: WorldValue>(&self, mut layer: Layer<T>) -> Layer<T> {
+		for stamp in &self.stamps {
+			stamp.apply(&mut layer);
+		}
+		layer
+	}
+}
+
+/// All layers of a generated world, along with the names of its places, its portals and its
+/// dungeons.
+#[derive(Clone)]
+pub struct WorldLayers {
+	pub water: Layer<WaterType>,
+	pub terrain: Layer<TerrainFeature>,
+	pub biome: Layer<Biome>,
+	pub detail: Layer<TerrainDetail>,
+	pub flora: Layer<Flora>,
+	pub mineral: Layer<Mineral>,
+	pub urban: Layer<Urban>,
+	pub special: Layer<Special>,
+	pub territory: Layer<TerritoryId>,
+	pub names: WorldNames,
+	pub portals: PortalNetwork,
+	pub dungeons: Dungeons,
+}
+
+impl WorldLayers {
+	/// Generates every layer of the world described by the [WorldGenConfig].
+	pub fn generate(config: &WorldGenConfig) -> Self {
+		let noise_gen = NoiseGenerator::new(config.seed);
+		let size = config.size;
+		let (base, detail, special) =
+			(config.base_scale(), config.detail_scale(), config.special_scale());
+		let smoothing =
+			MajorityFilter { neighborhood: Neighborhood::Moore, iterations: config.smoothing };
+
+		let water = if config.is_enabled(LayerKind::Water) {
+			config.stamped(
+				generate_water_layer(&noise_gen, base, size, config.preset.water_level())
+					.apply(smoothing),
+			)
+		} else {
+			Layer::new_base32(base, size)
+		};
+		let terrain = if config.is_enabled(LayerKind::Terrain) {
+			config.stamped(
+				generate_terrain_layer(&noise_gen, base, size, water.clone()).apply(smoothing),
+			)
+		} else {
+			Layer::new_base32(base, size)
+		};
+		let biome = if config.is_enabled(LayerKind::Biome) {
+			config.stamped(
+				generate_biome_layer(&noise_gen, base, size, water.clone(), terrain.clone())
+					.apply(smoothing),
+			)
+		} else {
+			Layer::new_base32(base, size)
+		};
+		let detail_layer = if config.is_enabled(LayerKind::Detail) {
+			config.stamped(generate_detail_layer(
+				&noise_gen,
+				detail,
+				size,
+				water.clone(),
+				terrain.clone(),
+				biome.clone(),
+			))
+		} else {
+			Layer::new_base32(detail, size)
+		};
+		let flora = if config.is_enabled(LayerKind::Flora) {
+			config.stamped(generate_flora_layer(
+				&noise_gen,
+				detail,
+				size,
+				water.clone(),
+				terrain.clone(),
+				biome.clone(),
+				detail_layer.clone(),
+			))
+		} else {
+			Layer::new_base32(detail, size)
+		};
+		// Volcanoes go first, as minerals form around them.
+		let volcanoes = if config.is_enabled(LayerKind::Special) {
+			config.stamped(place_volcanoes(&noise_gen, special, size, &water, &terrain))
+		} else {
+			Layer::new_base32(special, size)
+		};
+		let mineral = if config.is_enabled(LayerKind::Mineral) {
+			config.stamped(generate_mineral_layer(
+				&noise_gen,
+				detail,
+				size,
+				water.clone(),
+				terrain.clone(),
+				volcanoes.clone(),
+			))
+		} else {
+			Layer::new_base32(detail, size)
+		};
+		let urban = if config.is_enabled(LayerKind::Urban) {
+			config.stamped(generate_urban_layer(
+				&noise_gen,
+				detail,
+				size,
+				water.clone(),
+				terrain.clone(),
+				biome.clone(),
+				detail_layer.clone(),
+				flora.clone(),
+				mineral.clone(),
+			))
+		} else {
+			Layer::new_base32(detail, size)
+		};
+		let special = if config.is_enabled(LayerKind::Special) {
+			// Stamped sites are stamped again, in case other sites were placed over them.
+			config.stamped(generate_special_layer(
+				&noise_gen, volcanoes, &water, &terrain, &mineral, &urban,
+			))
+		} else {
+			volcanoes
+		};
+
+		let territory = if config.is_enabled(LayerKind::Territory) {
+			config.stamped(generate_territory_layer(
+				detail,
+				size,
+				water.clone(),
+				terrain.clone(),
+				urban.clone(),
+				config.kingdom_reach,
+			))
+		} else {
+			Layer::new_base32(detail, size)
+		};
+		let names = WorldNames::generate(&noise_gen, &water, &biome, &urban);
+		let portals = PortalNetwork::link(&noise_gen, &special);
+		let dungeons = Dungeons::new(&noise_gen, &special);
+
+		Self {
+			water,
+			terrain,
+			biome,
+			detail: detail_layer,
+			flora,
+			mineral,
+			urban,
+			special,
+			territory,
+			names,
+			portals,
+			dungeons,
+		}
+	}
+
+	/// Iterates the world position of every cell in the given layer.
+	pub fn positions(&self, kind: LayerKind) -> AllGridPositions {
+		match kind {
+			LayerKind::Water => self.water.positions(),
+			LayerKind::Terrain => self.terrain.positions(),
+			LayerKind::Biome => self.biome.positions(),
+			LayerKind::Detail => self.detail.positions(),
+			LayerKind::Flora => self.flora.positions(),
+			LayerKind::Mineral => self.mineral.positions(),
+			LayerKind::Urban => self.urban.positions(),
+			LayerKind::Special => self.special.positions(),
+			LayerKind::Territory => self.territory.positions(),
+		}
+	}
+
+	/// The cell size of the given layer.
+	pub fn scale(&self, kind: LayerKind) -> i64 {
+		match kind {
+			LayerKind::Water => self.water.scale(),
+			LayerKind::Terrain => self.terrain.scale(),
+			LayerKind::Biome => self.biome.scale(),
+			LayerKind::Detail => self.detail.scale(),
+			LayerKind::Flora => self.flora.scale(),
+			LayerKind::Mineral => self.mineral.scale(),
+			LayerKind::Urban => self.urban.scale(),
+			LayerKind::Special => self.special.scale(),
+			LayerKind::Territory => self.territory.scale(),
+		}
+	}
+}